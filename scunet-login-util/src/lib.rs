@@ -1,4 +1,7 @@
 //! 四川大学校园网登录工具库
+//!
+//! 登录的每一步都记录在同名的 `tracing` span 中：`wifi_check`、`portal_probe`、`page_info`、
+//! `encrypt`、`login_post` 和 `user_info`，日志中不会出现密码和 userIndex
 
 mod types;
 mod wifi;
@@ -7,6 +10,7 @@ use std::{thread::sleep, time::Duration};
 
 use anyhow::Result;
 use rsa::BigUint;
+use tracing::{debug, info, info_span, warn};
 use typed_builder::TypedBuilder;
 
 pub use crate::types::*;
@@ -20,10 +24,14 @@ const ONLINE_USER_INFO_URL: &str =
 
 const PAGE_INFO_URL: &str = "http://192.168.2.135/eportal/InterFace.do?method=pageInfo";
 
+const LOGOUT_URL: &str = "http://192.168.2.135/eportal/InterFace.do?method=logout";
+
 /// 用于登录四川大学校园网的工具结构体
 ///
 /// ## 使用例
-/// ```
+/// ```no_run
+/// use scunet_login_util::*;
+///
 /// let mut util = ScunetLoginUtil::builder()
 ///     .student_id("2021xxxxxxxxx")
 ///     .password("ilovescu!")
@@ -33,6 +41,7 @@ const PAGE_INFO_URL: &str = "http://192.168.2.135/eportal/InterFace.do?method=pa
 ///
 /// match util.login() {
 ///     // ...
+///     # _ => {}
 /// }
 /// ```
 #[derive(TypedBuilder)]
@@ -76,7 +85,13 @@ impl<'a> ScunetLoginUtil<'a> {
     /// 如果使用运营商登录失败且错误为 terminal failed，会自动回退到校园网登录
     ///
     /// ## 使用例
-    /// ```
+    /// ```no_run
+    /// # use scunet_login_util::*;
+    /// # let mut util = ScunetLoginUtil::builder()
+    /// #     .student_id("2021xxxxxxxxx")
+    /// #     .password("ilovescu!")
+    /// #     .service(Service::Internet)
+    /// #     .build();
     /// match util.login() {
     ///     Ok(LoginStatus::Success(user_info)) => {},
     ///     Ok(LoginStatus::HaveLoggedIn) => {},
@@ -84,13 +99,19 @@ impl<'a> ScunetLoginUtil<'a> {
     /// }
     /// ```
     pub fn login(&mut self) -> Result<LoginStatus> {
+        let _span = info_span!("login", service = ?self.service, on_boot = self.on_boot).entered();
+
         let query_string = match check_status(true, self.on_boot)? {
-            Status::LoggedIn(_) => return Ok(LoginStatus::HaveLoggedIn),
+            Status::LoggedIn(_) => {
+                info!("当前已登录");
+                return Ok(LoginStatus::HaveLoggedIn);
+            }
             Status::NotLoggedIn(qs) => qs,
         };
 
         // 加密后的密码长度以后应该不会变的吧...
         let password = if self.password.len() == 256 {
+            debug!("使用已加密的密码");
             self.password
         } else {
             &encrypt_password(self.password, &query_string)?
@@ -104,40 +125,107 @@ impl<'a> ScunetLoginUtil<'a> {
             ("passwordEncrypt", "true"),
         ];
 
-        let json: LoginResultJson = ureq::post(LOGIN_URL).send_form(&login_form)?.into_json()?;
+        let json: LoginResultJson = {
+            let _span = info_span!("login_post").entered();
+            let json: LoginResultJson = ureq::post(LOGIN_URL)
+                .send_form(&login_form)
+                .inspect_err(|e| warn!(error = %e, "登录请求失败"))?
+                .into_json()?;
+            debug!(message = %json.message, "登录请求已返回");
+            json
+        };
 
         match check_status(false, false)? {
             Status::LoggedIn(user_index) => {
-                Ok(LoginStatus::Success(get_user_info(&user_index, password, self.service)?))
+                info!("登录成功");
+                Ok(LoginStatus::Success(get_user_info(
+                    &user_index,
+                    password,
+                    self.service,
+                )?))
             }
             _ => {
                 let err = LoginError::Fail(json.message);
                 // 如果是教学区使用运营商登录失败，则回退到校园网
                 // 但只尝试一次 fallback，如果当前已经是校园网服务则不再尝试
-                if err.to_string().contains("terminal failed") && self.service != Service::Internet {
+                if err.to_string().contains("terminal failed") && self.service != Service::Internet
+                {
+                    warn!(error = %err, "运营商登录失败，回退到校园网重试");
                     self.service = Service::Internet;
                     // 递归调用，使用校园网重试
                     return self.login();
                 }
+                warn!(error = %err, "登录失败");
                 Err(err.into())
             }
         }
     }
+
+    /// 执行注销操作
+    ///
+    /// 当前未登录时返回 [`LogoutStatus::NotLoggedIn`]
+    ///
+    /// ## 使用例
+    /// ```no_run
+    /// use scunet_login_util::*;
+    ///
+    /// let util = ScunetLoginUtil::builder()
+    ///     .student_id("2021xxxxxxxxx")
+    ///     .password("ilovescu!")
+    ///     .service(Service::Internet)
+    ///     .build();
+    ///
+    /// match util.logout() {
+    ///     Ok(LogoutStatus::Success) => {},
+    ///     Ok(LogoutStatus::NotLoggedIn) => {},
+    ///     Err(e) => {},
+    /// }
+    /// ```
+    pub fn logout(&self) -> Result<LogoutStatus> {
+        let _span = info_span!("logout").entered();
+
+        let user_index = match check_status(false, false)? {
+            Status::LoggedIn(user_index) => user_index,
+            Status::NotLoggedIn(_) => {
+                info!("当前未登录，无需注销");
+                return Ok(LogoutStatus::NotLoggedIn);
+            }
+        };
+
+        let json: LogoutResultJson = ureq::post(LOGOUT_URL)
+            .send_form(&[("userIndex", user_index.as_str())])?
+            .into_json()?;
+
+        if json.result == "success" {
+            info!("注销成功");
+            Ok(LogoutStatus::Success)
+        } else {
+            warn!(message = %json.message, "注销失败");
+            Err(LoginError::Fail(json.message).into())
+        }
+    }
 }
 
 fn check_status(check_wifi: bool, on_boot: bool) -> Result<Status> {
     if check_wifi {
-        wifi::check_wifi(on_boot)?;
+        let _span = info_span!("wifi_check").entered();
+        wifi::check_wifi(on_boot).inspect_err(|e| warn!(error = %e, "Wi-Fi 检查未通过"))?;
     }
 
-    let res = ureq::get(BASE_URL).call()?;
+    let _span = info_span!("portal_probe").entered();
+    let res = ureq::get(BASE_URL)
+        .call()
+        .inspect_err(|e| warn!(error = %e, "无法访问认证页面"))?;
 
     if res.status() != 200 {
+        warn!(status = res.status(), "认证页面返回了异常的状态码");
         return Err(LoginError::TimeOut.into());
     }
     // 登录成功会重定向到 /eportal/success.jsp?userIndex=...
     // 链接不带 userIndex 查询参数则说明未登录
-    if res.get_url().contains('?') {
+    let logged_in = res.get_url().contains('?');
+    debug!(logged_in, "认证页面已响应");
+    if logged_in {
         let user_index = res.get_url().split_once('=').unwrap().1;
         Ok(Status::LoggedIn(user_index.to_string()))
     } else {
@@ -148,6 +236,7 @@ fn check_status(check_wifi: bool, on_boot: bool) -> Result<Status> {
 }
 
 fn get_user_info(user_index: &str, password: &str, service: Service) -> Result<OnlineUserInfo> {
+    let _span = info_span!("user_info").entered();
     let mut attempts = 0;
 
     loop {
@@ -156,7 +245,8 @@ fn get_user_info(user_index: &str, password: &str, service: Service) -> Result<O
             .into_json()?;
 
         if json.result == "success" {
-            let ball_info = serde_json::from_str::<Vec<BallInfoJson>>(json.ballInfo.as_ref().unwrap())?;
+            let ball_info =
+                serde_json::from_str::<Vec<BallInfoJson>>(json.ballInfo.as_ref().unwrap())?;
 
             // 教学区使用校园网会出现没有 ballInfo 的情况
             if !ball_info.is_empty() {
@@ -170,10 +260,13 @@ fn get_user_info(user_index: &str, password: &str, service: Service) -> Result<O
             json.service = service;
             json.ballInfo.take(); // 不想再多看一眼
 
+            info!(left_hour = ?json.left_hour, "已获取用户信息");
             return Ok(json);
         } else {
             attempts += 1;
+            debug!(attempts, result = %json.result, "用户信息尚未就绪，稍后重试");
             if attempts >= 5 {
+                warn!(attempts, "多次获取用户信息失败");
                 // 5 次了还让我 wait 那可以 414 了
                 return Err(LoginError::Fail("获取用户信息失败 (但可能已登录成功)".into()).into());
             }
@@ -188,10 +281,15 @@ fn encrypt_password(password: &str, query_string: &str) -> Result<String> {
 
     let mac_address = &query_string[begin..begin + end];
 
-    let res: PageInfo = ureq::post(PAGE_INFO_URL)
-        .send_form(&[("queryString", query_string)])?
-        .into_json()?;
+    let res: PageInfo = {
+        let _span = info_span!("page_info").entered();
+        ureq::post(PAGE_INFO_URL)
+            .send_form(&[("queryString", query_string)])
+            .inspect_err(|e| warn!(error = %e, "获取 pageInfo 失败"))?
+            .into_json()?
+    };
 
+    let _span = info_span!("encrypt").entered();
     let rsa_n = BigUint::parse_bytes(res.publicKeyModulus.as_bytes(), 16).unwrap();
     let rsa_e = BigUint::parse_bytes(res.publicKeyExponent.as_bytes(), 16).unwrap();
     let msg = BigUint::from_bytes_be(format!("{}>{}", password, mac_address).as_bytes());
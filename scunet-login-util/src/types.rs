@@ -53,6 +53,15 @@ pub enum LoginStatus {
     HaveLoggedIn,
 }
 
+/// 注销的结果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogoutStatus {
+    /// 注销成功
+    Success,
+    /// 本来就未登录
+    NotLoggedIn,
+}
+
 /// 登录时产生的错误
 #[derive(Debug, Error)]
 pub enum LoginError {
@@ -71,6 +80,12 @@ pub(crate) struct LoginResultJson {
     pub message: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct LogoutResultJson {
+    pub result: String,
+    pub message: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct BallInfoJson {
     pub value: Option<String>,
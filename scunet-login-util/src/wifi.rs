@@ -45,6 +45,7 @@ mod windows {
                     if attempts >= max_attempt {
                         return Err(last_error);
                     }
+                    tracing::debug!(attempts, error = %last_error, "Wi-Fi 检查失败，1 秒后重试");
                     sleep(Duration::from_secs(1));
                     continue;
                 }
@@ -58,6 +59,7 @@ mod windows {
                     if attempts >= max_attempt {
                         return Err(last_error);
                     }
+                    tracing::debug!(attempts, error = %last_error, "Wi-Fi 检查失败，1 秒后重试");
                     sleep(Duration::from_secs(1));
                     continue;
                 }
@@ -82,6 +84,7 @@ mod windows {
                     if attempts >= max_attempt {
                         return Err(last_error);
                     }
+                    tracing::debug!(attempts, error = %last_error, "Wi-Fi 检查失败，1 秒后重试");
                     sleep(Duration::from_secs(1));
                     continue;
                 }
@@ -105,9 +108,11 @@ mod windows {
                     if attempts >= max_attempt {
                         return Err(last_error);
                     }
+                    tracing::debug!(attempts, error = %last_error, "Wi-Fi 检查失败，1 秒后重试");
                     sleep(Duration::from_secs(1));
                     continue;
                 } else {
+                    tracing::debug!(attempts, "已连接到 SCUNET");
                     return Ok(());
                 }
             }
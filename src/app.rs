@@ -1,14 +1,23 @@
 use anyhow::Result;
+use chrono::Weekday;
 use eframe::*;
 use egui::*;
 use std::{
-    fs::read,
-    process::exit,
     sync::mpsc::{Receiver, Sender},
     thread,
 };
 
-use crate::{Toast, config::*};
+use crate::{
+    config::*,
+    credentials, diagnostics, exit_when_done, font,
+    history::{self, LeftHourHistory, Sample},
+    hooks::{self, HookEvent},
+    i18n::{self, Language, error_text, service_name, t, tf},
+    instance::{Command, Instance},
+    logging, logout, notification, on_login_success, password, profile,
+    schedule::ScheduleRule,
+    switch,
+};
 
 use scunet_login_util::*;
 
@@ -16,27 +25,57 @@ pub struct AutoScunetAppParam {
     pub config: AppConfig,
     pub logged_in: bool,
     pub not_connected_to_scunet: bool,
+    /// 配置文件读取失败的原因，此时使用的是默认配置
+    pub config_error: Option<String>,
+    pub instance: Instance,
 }
 
 pub struct AutoScunetApp {
     tx: Sender<Result<LoginStatus>>,
     rx: Receiver<Result<LoginStatus>>,
+    logout_tx: Sender<Result<LogoutStatus>>,
+    logout_rx: Receiver<Result<LogoutStatus>>,
+    instance: Instance,
 
     config: AppConfig,
+    /// 最近一次保存或读取到的配置，用于判断界面上有哪些未保存的修改
+    saved_config: AppConfig,
     logining: bool,
     status: String,
     show_setting_modal: bool,
+    history: LeftHourHistory,
+    show_history_window: bool,
+    show_profile_window: bool,
+    /// 导入导出配置的文件路径
+    profile_path: String,
+    /// 导出时去掉账号、token、Webhook 和钩子命令
+    profile_redact: bool,
+    show_diagnostics_window: bool,
+    /// 正在输入的主密码
+    passphrase: String,
 }
 
 impl AutoScunetApp {
     pub fn new(cc: &CreationContext<'_>, param: AutoScunetAppParam) -> Self {
-        set_font(&cc.egui_ctx);
+        let font_warning = set_font(&cc.egui_ctx, &param.config.font_path);
+        apply_appearance(&cc.egui_ctx, &param.config);
         let (tx, rx) = std::sync::mpsc::channel();
+        let (logout_tx, logout_rx) = std::sync::mpsc::channel();
+
+        let ctx = cc.egui_ctx.clone();
+        param.instance.set_waker(move || ctx.request_repaint());
+
+        let sender = param.instance.sender();
+        watch_config(move || sender.send(Command::ReloadConfig));
 
-        let status = if param.logged_in {
-            "你目前已登录到 SCUNET!".to_string()
+        let status = if let Some(warning) = font_warning {
+            warning.to_string()
+        } else if let Some(error) = param.config_error {
+            error
+        } else if param.logged_in {
+            t("你目前已登录到 SCUNET!").to_string()
         } else if param.not_connected_to_scunet {
-            "你目前未连接到 SCUNET!".to_string()
+            t("你目前未连接到 SCUNET!").to_string()
         } else {
             Default::default()
         };
@@ -44,27 +83,66 @@ impl AutoScunetApp {
         Self {
             tx,
             rx,
+            logout_tx,
+            logout_rx,
+            instance: param.instance,
+            saved_config: param.config.clone(),
             config: param.config,
             logining: false,
             status,
             show_setting_modal: false,
+            history: history::load_history().unwrap_or_default(),
+            show_history_window: false,
+            show_profile_window: false,
+            profile_path: default_profile_path(),
+            profile_redact: true,
+            show_diagnostics_window: false,
+            passphrase: String::new(),
         }
     }
 
     pub fn login(&self, ctx: Context) {
+        self.spawn_login(ctx, None);
+    }
+
+    /// 更换服务后重新登录，原来的服务会先注销
+    fn switch_service(&mut self, ctx: Context, service: Service) {
+        let previous = self.config.service;
+        self.config.service = service;
+        self.spawn_login(ctx, (previous != service).then_some(previous));
+    }
+
+    fn spawn_login(&self, ctx: Context, logout_first: Option<Service>) {
         let tx = self.tx.clone();
-        let student_id = self.config.student_id.clone();
-        let password = self.config.password.clone();
-        let service = self.config.service;
+        let config = self.config.clone();
 
         thread::spawn(move || {
-            let mut login_util = ScunetLoginUtil::builder()
-                .student_id(&student_id)
-                .password(&password)
-                .service(service)
-                .build();
+            // 手动换服务时按所选的登录，否则和后台模式一样先处理月初切换回校园网
+            let service = match logout_first {
+                Some(previous) => {
+                    logout(&config, previous).ok();
+                    config.service
+                }
+                None => switch::prepare_service(&config),
+            };
+
+            let result = password::resolve(&config).and_then(|password| {
+                ScunetLoginUtil::builder()
+                    .student_id(&config.student_id)
+                    .password(&password)
+                    .service(service)
+                    .build()
+                    .login()
+            });
+            password::forget_if_rejected(&result);
+            let result = result.map(|status| match status {
+                LoginStatus::Success(user_info) => {
+                    LoginStatus::Success(switch::apply(&config, user_info))
+                }
+                status => status,
+            });
 
-            tx.send(login_util.login()).unwrap();
+            tx.send(result).unwrap();
             ctx.request_repaint();
         });
     }
@@ -72,43 +150,86 @@ impl AutoScunetApp {
     pub fn handle_login_result(&mut self) {
         if let Ok(response) = self.rx.try_recv() {
             match response {
-                Ok(LoginStatus::HaveLoggedIn) => {
-                    self.status = "配置已更新".to_string();
-                    save_config(&self.config).unwrap();
-                }
+                Ok(LoginStatus::HaveLoggedIn) => self.save_config(),
                 Ok(LoginStatus::Success(user_info)) => {
-                    self.config.password = user_info.encrypted_password;
-                    save_config(&self.config).unwrap();
-                    Toast::success(
-                        user_info.userName,
-                        user_info.welcomeTip,
-                        user_info.left_hour,
-                        user_info.service,
-                        &self.config,
-                    );
-                    exit(0);
+                    // 保存失败时留在窗口上显示原因
+                    match on_login_success(&mut self.config, user_info) {
+                        Ok(()) => exit_when_done(0),
+                        Err(e) => self.status = format!("{:#}", e),
+                    }
                 }
                 Err(err) => {
-                    self.status = err.to_string();
+                    self.status = error_text(&err);
+                    hooks::run(HookEvent::Failure {
+                        error: self.status.clone(),
+                    });
                 }
             }
             self.logining = false;
         }
     }
 
+    pub fn logout(&self, ctx: Context) {
+        let tx = self.logout_tx.clone();
+        let config = self.config.clone();
+
+        thread::spawn(move || {
+            tx.send(logout(&config, config.service)).unwrap();
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn handle_logout_result(&mut self) {
+        if let Ok(response) = self.logout_rx.try_recv() {
+            self.status = match response {
+                Ok(LogoutStatus::Success) => t("已注销").to_string(),
+                Ok(LogoutStatus::NotLoggedIn) => t("你目前未登录").to_string(),
+                Err(err) => error_text(&err),
+            };
+        }
+    }
+
+    /// 处理后启动的实例转发过来的命令
+    fn handle_commands(&mut self, ctx: &Context) {
+        while let Ok(command) = self.instance.commands.try_recv() {
+            match command {
+                Command::Show => {
+                    ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
+                    ctx.send_viewport_cmd(ViewportCommand::Focus);
+                }
+                Command::Login if !self.logining => {
+                    self.status = t("正在登录...").to_string();
+                    self.logining = true;
+                    self.login(ctx.clone());
+                }
+                Command::SwitchService(service) if !self.logining => {
+                    self.status = t("正在切换服务...").to_string();
+                    self.logining = true;
+                    self.switch_service(ctx.clone(), service);
+                }
+                Command::Login | Command::SwitchService(_) => {}
+                Command::Logout => {
+                    self.status = t("正在注销...").to_string();
+                    self.logout(ctx.clone());
+                }
+                Command::ReloadConfig => self.reload_config(ctx),
+            }
+        }
+    }
+
     fn render_header(&mut self, ui: &mut Ui) {
         ui.horizontal(|ui| {
-            ui.heading("登录到 SCUNET");
+            ui.heading(t("登录到 SCUNET"));
             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                 if self.config.show_github_button
                     && ui
                         .button(format!(" {} ", special_emojis::GITHUB))
-                        .on_hover_text("查看 GitHub 仓库")
+                        .on_hover_text(t("查看 GitHub 仓库"))
                         .clicked()
                 {
                     webbrowser::open(GITHUB_REPO).unwrap();
                 }
-                if ui.button("设置").clicked() {
+                if ui.button(t("设置")).clicked() {
                     self.show_setting_modal = true;
                 }
             })
@@ -116,122 +237,690 @@ impl AutoScunetApp {
     }
 
     fn render_login_form(&mut self, ui: &mut Ui, ctx: &Context) {
-        ui.horizontal(|ui| {
-            ui.label("学号:");
-            ui.text_edit_singleline(&mut self.config.student_id);
-        });
-        ui.horizontal(|ui| {
-            ui.label("密码:");
-            TextEdit::singleline(&mut self.config.password)
-                .password(true)
-                .ui(ui);
-        });
+        if credentials::is_locked(&self.config) {
+            self.render_unlock_form(ui);
+        } else {
+            self.render_account_form(ui);
+        }
         ui.horizontal(|ui| {
             use Service::*;
             ComboBox::from_label("")
-                .selected_text(self.config.service.to_str())
+                .selected_text(service_name(self.config.service))
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.config.service, Internet, "校园网");
-                    ui.selectable_value(&mut self.config.service, ChinaMobile, "中国移动");
-                    ui.selectable_value(&mut self.config.service, ChinaTelecom, "中国电信");
-                    ui.selectable_value(&mut self.config.service, ChinaUnicom, "中国联通");
+                    for service in [Internet, ChinaMobile, ChinaTelecom, ChinaUnicom] {
+                        ui.selectable_value(
+                            &mut self.config.service,
+                            service,
+                            service_name(service),
+                        );
+                    }
                 });
-            if ui.checkbox(&mut self.config.on_boot, "开机启动").changed() {
+            if ui
+                .checkbox(&mut self.config.on_boot, t("开机启动"))
+                .changed()
+            {
                 on_boot_change(self.config.on_boot)
             }
             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                if ui
-                    .add_enabled(!self.logining, Button::new("登录"))
-                    .clicked()
-                {
-                    self.status = "正在登录...".to_string();
+                let can_login = !self.logining && !credentials::is_locked(&self.config);
+                if ui.add_enabled(can_login, Button::new(t("登录"))).clicked() {
+                    self.status = t("正在登录...").to_string();
                     self.logining = true;
                     self.login(ctx.clone());
                 }
+                // 紧凑布局没有标题行，设置按钮放在这里
+                if self.config.compact && ui.button("⚙").on_hover_text(t("设置")).clicked() {
+                    self.show_setting_modal = true;
+                }
             });
         });
     }
 
+    fn render_account_form(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(t("学号:"));
+            ui.text_edit_singleline(&mut self.config.student_id);
+        });
+        ui.horizontal(|ui| {
+            ui.label(t("密码:"));
+            if password::has_external_source(&self.config) {
+                ui.weak(t("登录时从外部来源读取"));
+            } else {
+                TextEdit::singleline(&mut self.config.password)
+                    .password(true)
+                    .ui(ui);
+            }
+        });
+    }
+
+    /// 账号已加密时，先输入主密码解锁
+    fn render_unlock_form(&mut self, ui: &mut Ui) {
+        ui.label(t("账号已加密，请输入主密码解锁"));
+        ui.horizontal(|ui| {
+            ui.label(t("主密码:"));
+            let response = TextEdit::singleline(&mut self.passphrase)
+                .password(true)
+                .desired_width(120.0)
+                .ui(ui);
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if ui.button(t("解锁")).clicked() || submitted {
+                match credentials::unlock(&mut self.config, &self.passphrase) {
+                    Ok(()) => {
+                        self.saved_config = self.config.clone();
+                        self.status.clear();
+                    }
+                    Err(e) => self.status = format!("{:#}", e),
+                }
+                self.passphrase.clear();
+            }
+        });
+    }
+
     fn render_setting_modal(&mut self, ctx: &Context) {
         let was_settings_open = self.show_setting_modal;
+        let mut save_now = false;
 
-        Window::new("设置")
+        Window::new(t("设置"))
             .open(&mut self.show_setting_modal)
             .max_width(200.0)
             .collapsible(false)
             .resizable(false)
             .pivot(Align2::CENTER_CENTER)
+            .show(ctx, |ui| {
+                // 设置项比默认窗口高，超出的部分滚动显示
+                ScrollArea::vertical().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(t("语言"));
+                        let previous = self.config.language;
+                        ComboBox::from_id_salt("language")
+                            .selected_text(self.config.language.name())
+                            .show_ui(ui, |ui| {
+                                for language in Language::ALL {
+                                    ui.selectable_value(
+                                        &mut self.config.language,
+                                        language,
+                                        language.name(),
+                                    );
+                                }
+                            });
+                        if self.config.language != previous {
+                            i18n::set_language(self.config.language);
+                        }
+                    });
+                    render_appearance(ui, ctx, &mut self.config);
+                    ui.horizontal(|ui| {
+                        ui.label(t("问候称呼"));
+                        ui.text_edit_singleline(&mut self.config.greeting_name)
+                            .on_hover_text(t("留空则使用真实姓名"))
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t("开机启动延迟"));
+                        ui.add(
+                            DragValue::new(&mut self.config.on_boot_delay)
+                                .range(0..=5)
+                                .suffix(t(" 秒"))
+                                .speed(0.1),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t("低时长提醒"));
+                        ui.add(
+                            DragValue::new(&mut self.config.low_hour_threshold)
+                                .range(0..=200)
+                                .suffix(t(" 小时"))
+                                .speed(0.5),
+                        )
+                        .on_hover_text(t("剩余时长低于该值时发送通知，设为 0 则不提醒"));
+                        if ui.button(t("趋势")).clicked() {
+                            self.show_history_window = true;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t("时长用完后切换到"));
+                        ComboBox::from_id_salt("auto_switch_service")
+                            .selected_text(
+                                self.config
+                                    .auto_switch_service
+                                    .map_or(t("不切换"), service_name),
+                            )
+                            .show_ui(ui, |ui| {
+                                use Service::*;
+                                let value = &mut self.config.auto_switch_service;
+                                ui.selectable_value(value, None, t("不切换"));
+                                for service in [ChinaMobile, ChinaTelecom, ChinaUnicom] {
+                                    ui.selectable_value(
+                                        value,
+                                        Some(service),
+                                        service_name(service),
+                                    );
+                                }
+                            });
+                    });
+                    ui.add_enabled(
+                        self.config.auto_switch_service.is_some(),
+                        Checkbox::new(
+                            &mut self.config.switch_back_on_reset,
+                            t("每月重置后切换回校园网"),
+                        ),
+                    );
+                    CollapsingHeader::new(t("计划任务"))
+                        .show(ui, |ui| render_schedule(ui, &mut self.config))
+                        .header_response
+                        .on_hover_text(t("仅在以 --daemon 后台模式运行时生效"));
+                    ui.horizontal(|ui| {
+                        if ui.button(t("导入/导出配置...")).clicked() {
+                            self.show_profile_window = true;
+                        }
+                        if ui.button(t("日志与诊断...")).clicked() {
+                            self.show_diagnostics_window = true;
+                        }
+                    });
+                    CollapsingHeader::new(t("账号加密")).show(ui, |ui| {
+                        save_now |= render_encryption(
+                            ui,
+                            &mut self.config,
+                            &mut self.passphrase,
+                            &mut self.status,
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .checkbox(&mut self.config.enable_toast, t("启用通知"))
+                            .changed()
+                        {
+                            notification::init(&self.config);
+                            save_now = true;
+                        }
+                        if ui
+                            .checkbox(&mut self.config.show_github_button, t("显示 GitHub 按钮"))
+                            .changed()
+                        {
+                            save_now = true;
+                        }
+                    })
+                })
+            });
+
+        if was_settings_open && !self.show_setting_modal {
+            self.config.greeting_name = self.config.greeting_name.trim().into();
+            save_now = true;
+        }
+        if save_now {
+            self.save_config();
+        }
+    }
+
+    /// 保存配置，结果显示在状态栏
+    fn save_config(&mut self) {
+        self.status = match save_config(&self.config) {
+            Ok(()) => {
+                self.saved_config = self.config.clone();
+                t("配置已更新").to_string()
+            }
+            Err(e) => format!("{}: {:#}", t("保存配置失败"), e),
+        };
+    }
+
+    /// 配置文件被其它程序修改后重新读取，界面上未保存的修改优先
+    fn reload_config(&mut self, ctx: &Context) {
+        let merged = reload_config().and_then(|theirs| {
+            Ok((
+                merge_config(&self.saved_config, &self.config, &theirs)?,
+                theirs,
+            ))
+        });
+
+        self.status = match merged {
+            Ok(((config, kept), theirs)) => {
+                apply_config(&config);
+                apply_appearance(ctx, &config);
+                self.config = config;
+                self.saved_config = theirs;
+                if kept {
+                    t("配置文件已更新，界面上未保存的修改已保留").to_string()
+                } else {
+                    t("已重新加载配置文件").to_string()
+                }
+            }
+            Err(e) => format!("{:#}", e),
+        };
+    }
+
+    /// 记下窗口当前的位置和大小，关闭或保存配置时一并保存
+    fn track_window(&mut self, ctx: &Context) {
+        let (outer, inner) = ctx.input(|i| (i.viewport().outer_rect, i.viewport().inner_rect));
+        if let Some(rect) = outer {
+            self.config.window_pos = Some([rect.min.x, rect.min.y]);
+        }
+        if let Some(rect) = inner.filter(|_| self.config.resizable) {
+            self.config.window_size = Some([rect.width(), rect.height()]);
+        }
+    }
+
+    fn render_profile_window(&mut self, ctx: &Context) {
+        let mut export = false;
+        let mut import = false;
+
+        Window::new(t("导入/导出配置"))
+            .open(&mut self.show_profile_window)
+            .collapsible(false)
+            .resizable(false)
+            .pivot(Align2::CENTER_CENTER)
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label("问候称呼");
-                    ui.text_edit_singleline(&mut self.config.greeting_name)
-                        .on_hover_text("留空则使用真实姓名")
+                    ui.label(t("文件"));
+                    ui.text_edit_singleline(&mut self.profile_path);
                 });
+                ui.checkbox(
+                    &mut self.profile_redact,
+                    t("导出时不包含账号、token、Webhook 和钩子命令"),
+                );
                 ui.horizontal(|ui| {
-                    ui.label("开机启动延迟");
-                    ui.add(
-                        DragValue::new(&mut self.config.on_boot_delay)
-                            .range(0..=5)
-                            .suffix(" 秒")
-                            .speed(0.1),
-                    );
+                    let has_path = !self.profile_path.trim().is_empty();
+                    export = ui.add_enabled(has_path, Button::new(t("导出"))).clicked();
+                    import = ui
+                        .add_enabled(has_path, Button::new(t("导入")))
+                        .on_hover_text(t("导入后会覆盖当前的设置"))
+                        .clicked();
                 });
-                ui.horizontal(|ui| {
-                    if ui
-                        .checkbox(&mut self.config.enable_toast, "启用通知")
-                        .changed()
-                    {
-                        *IS_TOAST_ENABLED.write().unwrap() = self.config.enable_toast;
-                        save_config(&self.config).unwrap();
+            });
+
+        let path = std::path::PathBuf::from(self.profile_path.trim());
+        if export {
+            self.status = match profile::export(&self.config, &path, self.profile_redact) {
+                Ok(()) => tf("配置已导出到 {}", &[&path.display()]),
+                Err(e) => format!("{:#}", e),
+            };
+        }
+        if import {
+            match profile::import(&self.config, &path) {
+                Ok(config) => {
+                    apply_config(&config);
+                    apply_appearance(ctx, &config);
+                    self.config = config;
+                    self.save_config();
+                }
+                Err(e) => self.status = format!("{:#}", e),
+            }
+        }
+    }
+
+    fn render_diagnostics_window(&mut self, ctx: &Context) {
+        if !self.show_diagnostics_window {
+            return;
+        }
+        // 日志在其它线程中写入，打开窗口时定时刷新
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+
+        Window::new(t("日志与诊断"))
+            .open(&mut self.show_diagnostics_window)
+            .collapsible(false)
+            .default_size([480.0, 360.0])
+            .pivot(Align2::CENTER_CENTER)
+            .show(ctx, |ui| {
+                ui.heading(t("最近一次登录"));
+                match diagnostics::last_login() {
+                    Some(attempt) => render_login_attempt(ui, &attempt),
+                    None => {
+                        ui.label(t("还没有登录记录"));
                     }
-                    if ui
-                        .checkbox(&mut self.config.show_github_button, "显示 GitHub 按钮")
-                        .changed()
-                    {
-                        save_config(&self.config).unwrap();
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.heading(t("最近的日志"));
+                    if ui.button(t("复制到剪贴板")).clicked() {
+                        ctx.copy_text(diagnostics::report());
+                        self.status = t("已复制诊断信息").to_string();
                     }
-                })
+                });
+                ScrollArea::both()
+                    .auto_shrink(false)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in logging::recent_lines() {
+                            ui.add(Label::new(RichText::new(line).monospace()).extend());
+                        }
+                    });
             });
+    }
 
-        if was_settings_open && !self.show_setting_modal {
-            self.config.greeting_name = self.config.greeting_name.trim().into();
-            save_config(&self.config).unwrap();
-            self.status = "配置已更新".into();
-        }
+    fn render_history_window(&mut self, ctx: &Context) {
+        Window::new(t("剩余时长"))
+            .open(&mut self.show_history_window)
+            .collapsible(false)
+            .resizable(false)
+            .pivot(Align2::CENTER_CENTER)
+            .show(ctx, |ui| {
+                let Some(latest) = self.history.latest() else {
+                    ui.label(t("暂无记录，登录校园网后会自动记录"));
+                    return;
+                };
+                ui.label(tf("当前剩余: {} 小时", &[&latest.left_hour]));
+                ui.label(match self.history.estimate_exhaustion() {
+                    Some(time) => tf("预计耗尽: {}", &[&time.format("%Y-%m-%d %H:%M")]),
+                    None => t("预计耗尽: 暂无法估算").to_string(),
+                });
+                draw_left_hour_chart(ui, &self.history.samples);
+            });
     }
 }
 
 impl App for AutoScunetApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.handle_commands(ctx);
         self.handle_login_result();
+        self.handle_logout_result();
+        self.track_window(ctx);
 
         CentralPanel::default().show(ctx, |ui| {
-            self.render_header(ui);
+            if !self.config.compact {
+                self.render_header(ui);
+            }
             self.render_login_form(ui, ctx);
             ui.add_space(8.0);
             ui.vertical_centered_justified(|ui| ui.add(Label::new(&self.status)));
         });
 
         self.render_setting_modal(ctx);
+        self.render_history_window(ctx);
+        self.render_profile_window(ctx);
+        self.render_diagnostics_window(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&glow::Context>) {
+        save_window_state(self.config.window_pos, self.config.window_size).ok();
     }
 }
 
-fn set_font(cc: &Context) {
-    let mut fonts = FontDefinitions::default();
+/// 登录的每一步的耗时和结果
+fn render_login_attempt(ui: &mut Ui, attempt: &diagnostics::LoginAttempt) {
+    ui.label(format!(
+        "{} {}",
+        attempt.time.format("%Y-%m-%d %H:%M:%S"),
+        attempt.service
+    ));
+    Grid::new("login_steps").striped(true).show(ui, |ui| {
+        for step in &attempt.steps {
+            ui.label(step.label());
+            ui.label(diagnostics::format_duration(step.duration));
+            match &step.error {
+                Some(error) => {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("✘ {}", error))
+                }
+                None => ui.label("✔"),
+            };
+            ui.end_row();
+        }
+        ui.strong(t("合计"));
+        ui.strong(match attempt.total {
+            Some(total) => diagnostics::format_duration(total),
+            None => t("进行中").to_string(),
+        });
+        ui.end_row();
+    });
+    if attempt.failed {
+        ui.colored_label(ui.visuals().error_fg_color, &attempt.outcome);
+    } else {
+        ui.label(&attempt.outcome);
+    }
+}
+
+/// 导入导出配置的默认文件，放在主目录下方便拷走
+fn default_profile_path() -> String {
+    dirs::home_dir()
+        .map(|dir| dir.join("auto-scunet-profile.toml"))
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+fn apply_appearance(ctx: &Context, config: &AppConfig) {
+    ctx.set_zoom_factor(config.ui_scale);
+    ctx.set_theme(config.theme);
+}
 
-    #[cfg(windows)]
-    let font_paths = ["C:/Windows/Fonts/msyh.ttc"];
-    #[cfg(not(windows))]
-    let font_paths = [
-        "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc", // Arch Linux: pacman -S noto-fonts-cjk
-        "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc", // Ubuntu 22.04: apt install fonts-noto-cjk
+fn render_appearance(ui: &mut Ui, ctx: &Context, config: &mut AppConfig) {
+    const SCALES: [f32; 6] = [1.0, 1.25, 1.5, 1.75, 2.0, 2.5];
+    const THEMES: [(AppTheme, &str); 3] = [
+        (AppTheme::System, "跟随系统"),
+        (AppTheme::Light, "浅色"),
+        (AppTheme::Dark, "深色"),
     ];
 
-    let font_data = font_paths.iter().find_map(|path| read(path).ok()).unwrap();
+    let previous = (config.ui_scale, config.theme);
 
-    fonts
-        .font_data
-        .insert("Custom".to_owned(), FontData::from_owned(font_data).into());
+    ui.horizontal(|ui| {
+        ui.label(t("界面缩放"));
+        ComboBox::from_id_salt("ui_scale")
+            .width(60.0)
+            .selected_text(format!("{}%", (config.ui_scale * 100.0).round()))
+            .show_ui(ui, |ui| {
+                for scale in SCALES {
+                    let label = format!("{}%", scale * 100.0);
+                    ui.selectable_value(&mut config.ui_scale, scale, label);
+                }
+            });
+        ui.label(t("主题"));
+        ComboBox::from_id_salt("theme")
+            .width(60.0)
+            .selected_text(t(THEMES
+                .iter()
+                .find(|(theme, _)| *theme == config.theme)
+                .unwrap()
+                .1))
+            .show_ui(ui, |ui| {
+                for (theme, label) in THEMES {
+                    ui.selectable_value(&mut config.theme, theme, t(label));
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        if ui
+            .checkbox(&mut config.resizable, t("可调整窗口大小"))
+            .changed()
+        {
+            ctx.send_viewport_cmd(ViewportCommand::Resizable(config.resizable));
+            ctx.send_viewport_cmd(ViewportCommand::EnableButtons {
+                close: true,
+                minimized: false,
+                maximize: config.resizable,
+            });
+        }
+        ui.checkbox(&mut config.compact, t("紧凑布局"));
+    });
+
+    if (config.ui_scale, config.theme) != previous {
+        apply_appearance(ctx, config);
+    }
+}
+
+/// 启用或取消账号加密，返回是否需要保存配置
+fn render_encryption(
+    ui: &mut Ui,
+    config: &mut AppConfig,
+    passphrase: &mut String,
+    status: &mut String,
+) -> bool {
+    if config.credentials.is_some() {
+        ui.label(t("学号和密码已用主密码加密保存"));
+        let unlocked = !credentials::is_locked(config);
+        if ui
+            .add_enabled(unlocked, Button::new(t("取消加密")))
+            .clicked()
+        {
+            credentials::disable(config);
+            return true;
+        }
+        return false;
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(t("主密码:"));
+        TextEdit::singleline(passphrase).password(true).ui(ui);
+    });
+    if !ui
+        .add_enabled(!passphrase.is_empty(), Button::new(t("启用加密")))
+        .on_hover_text(t("忘记主密码后需要重新填写学号和密码"))
+        .clicked()
+    {
+        return false;
+    }
+    let result = credentials::enable(config, passphrase);
+    passphrase.clear();
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            *status = format!("{:#}", e);
+            false
+        }
+    }
+}
+
+fn render_schedule(ui: &mut Ui, config: &mut AppConfig) {
+    const DAYS: [(Weekday, &str); 7] = [
+        (Weekday::Mon, "一"),
+        (Weekday::Tue, "二"),
+        (Weekday::Wed, "三"),
+        (Weekday::Thu, "四"),
+        (Weekday::Fri, "五"),
+        (Weekday::Sat, "六"),
+        (Weekday::Sun, "日"),
+    ];
+
+    let mut removed = None;
+
+    for (i, rule) in config.schedule.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            for (day, label) in DAYS {
+                let selected = rule.days.contains(&day);
+                if ui.selectable_label(selected, t(label)).clicked() {
+                    if selected {
+                        rule.days.retain(|d| *d != day);
+                    } else {
+                        rule.days.push(day);
+                    }
+                }
+            }
+        })
+        .response
+        .on_hover_text(t("不选则每天生效"));
+        ui.horizontal(|ui| {
+            TextEdit::singleline(&mut rule.login_at)
+                .desired_width(40.0)
+                .ui(ui);
+            ui.label("-");
+            TextEdit::singleline(&mut rule.logout_at)
+                .desired_width(40.0)
+                .ui(ui);
+            ComboBox::from_id_salt(("schedule_service", i))
+                .width(60.0)
+                .selected_text(service_name(rule.service))
+                .show_ui(ui, |ui| {
+                    use Service::*;
+                    for service in [Internet, ChinaMobile, ChinaTelecom, ChinaUnicom] {
+                        ui.selectable_value(&mut rule.service, service, service_name(service));
+                    }
+                });
+            if ui.button(t("删除")).clicked() {
+                removed = Some(i);
+            }
+        });
+        if !rule.is_valid() {
+            ui.colored_label(ui.visuals().error_fg_color, t("时间格式应为 HH:MM"));
+        }
+        ui.separator();
+    }
+
+    if let Some(i) = removed {
+        config.schedule.remove(i);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button(t("添加时段")).clicked() {
+            config.schedule.push(ScheduleRule::default());
+        }
+        ui.label(t("检查间隔"));
+        ui.add(
+            DragValue::new(&mut config.watch_interval)
+                .range(10..=3600)
+                .suffix(t(" 秒")),
+        );
+    });
+}
+
+fn draw_left_hour_chart(ui: &mut Ui, samples: &[Sample]) {
+    let (rect, _) = ui.allocate_exact_size(vec2(240.0, 80.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+
+    painter.rect_stroke(
+        rect,
+        2.0,
+        visuals.widgets.noninteractive.bg_stroke,
+        StrokeKind::Inside,
+    );
+
+    if samples.len() < 2 {
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            t("记录不足"),
+            FontId::proportional(12.0),
+            visuals.weak_text_color(),
+        );
+        return;
+    }
+
+    let (start, end) = (samples[0].time, samples[samples.len() - 1].time);
+    let max_hour = samples.iter().map(|s| s.left_hour).fold(1.0, f64::max);
+
+    let points = samples
+        .iter()
+        .map(|s| {
+            let x = if end > start {
+                (s.time - start) as f32 / (end - start) as f32
+            } else {
+                0.0
+            };
+            let y = (s.left_hour / max_hour) as f32;
+            pos2(
+                rect.left() + x * rect.width(),
+                rect.bottom() - y * rect.height(),
+            )
+        })
+        .collect();
+
+    painter.add(Shape::line(
+        points,
+        Stroke::new(1.5, visuals.selection.bg_fill),
+    ));
+    painter.text(
+        rect.left_top() + vec2(4.0, 2.0),
+        Align2::LEFT_TOP,
+        tf("{} 小时", &[&max_hour]),
+        FontId::proportional(10.0),
+        visuals.weak_text_color(),
+    );
+}
+
+/// 设置中文字体，找不到时使用 egui 自带的字体并返回警告
+fn set_font(cc: &Context, font_path: &str) -> Option<&'static str> {
+    let Some(font) = font::find_cjk_font(font_path) else {
+        // 自带字体显示不了中文，只好让界面改用英文，通知不受影响
+        i18n::set_thread_language(Some(Language::English));
+        return Some(t("找不到中文字体，请在配置文件中设置 font_path"));
+    };
+
+    let mut fonts = FontDefinitions::default();
+
+    fonts.font_data.insert(
+        "Custom".to_owned(),
+        FontData {
+            index: font.index,
+            ..FontData::from_owned(font.data)
+        }
+        .into(),
+    );
 
     fonts
         .families
@@ -240,5 +929,5 @@ fn set_font(cc: &Context) {
         .insert(0, "Custom".to_owned());
 
     cc.set_fonts(fonts);
-    cc.set_pixels_per_point(1.25);
+    None
 }
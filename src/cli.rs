@@ -0,0 +1,196 @@
+//! 命令行参数
+//!
+//! 常用的设置可以用命令行参数或 `AUTO_SCUNET_*` 环境变量临时覆盖，
+//! 优先级为：默认值 < 配置文件 < 环境变量 < 命令行参数。覆盖的值只在本次运行中生效，不会写回配置文件
+
+use std::{path::PathBuf, sync::LazyLock};
+
+use clap::{Args, Parser};
+
+use scunet_login_util::Service;
+
+use crate::{
+    AppConfig,
+    i18n::Language,
+    instance,
+    notification::{NotificationEvent, NotifierKind, notify},
+};
+
+pub static CLI: LazyLock<Cli> = LazyLock::new(|| {
+    Cli::try_parse().unwrap_or_else(|e| {
+        // Windows 上没有控制台，参数有误时只能通过通知告诉用户
+        if e.use_stderr() {
+            notify(NotificationEvent::Error {
+                message: e.to_string(),
+            });
+        }
+        e.exit()
+    })
+});
+
+/// 自动登录 SCUNET
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// 开机自启动时使用：没有连接到校园网时直接退出
+    #[arg(long, hide = true)]
+    pub boot: bool,
+
+    /// 登录后退出，已有实例在运行时让它重新登录
+    #[arg(long, conflicts_with = "logout")]
+    pub login: bool,
+
+    /// 注销当前登录后退出
+    #[arg(long)]
+    pub logout: bool,
+
+    /// 以后台模式运行：不显示窗口，按计划任务登录和注销
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// 使用指定的配置文件
+    #[arg(long, env = "AUTO_SCUNET_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// 便携模式：配置文件和运行数据都放在程序所在目录
+    #[arg(long, conflicts_with = "config")]
+    pub portable: bool,
+
+    /// 把当前配置导出到文件后退出
+    #[arg(long, value_name = "PATH", conflicts_with = "import")]
+    pub export: Option<PathBuf>,
+
+    /// 导出时不包含学号、密码、HTTP 接口的 token、Webhook 和钩子命令
+    #[arg(long, requires = "export")]
+    pub redact: bool,
+
+    /// 从导出的文件导入配置后退出
+    #[arg(long, value_name = "PATH")]
+    pub import: Option<PathBuf>,
+
+    /// 登录时从标准输入读取密码
+    #[arg(long, conflicts_with = "password")]
+    pub password_stdin: bool,
+
+    #[command(flatten)]
+    pub overrides: Overrides,
+}
+
+/// 覆盖配置文件中对应项的参数
+#[derive(Debug, Args)]
+#[command(next_help_heading = "覆盖配置")]
+pub struct Overrides {
+    /// 学号
+    #[arg(long, env = "AUTO_SCUNET_STUDENT_ID")]
+    pub student_id: Option<String>,
+
+    /// 密码
+    #[arg(long, env = "AUTO_SCUNET_PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+
+    /// 登录时运行该命令，用输出的第一行作为密码
+    #[arg(long, env = "AUTO_SCUNET_PASSWORD_COMMAND", value_name = "COMMAND")]
+    pub password_command: Option<String>,
+
+    /// 登录时读取该文件的第一行作为密码
+    #[arg(long, env = "AUTO_SCUNET_PASSWORD_FILE", value_name = "PATH")]
+    pub password_file: Option<String>,
+
+    /// 登录的服务: internet, china_mobile, china_telecom, china_unicom
+    #[arg(long, env = "AUTO_SCUNET_SERVICE", value_parser = parse_service)]
+    pub service: Option<Service>,
+
+    /// 界面和通知的语言: zh-CN, en
+    #[arg(long, env = "AUTO_SCUNET_LANGUAGE", value_parser = parse_value::<Language>)]
+    pub language: Option<Language>,
+
+    /// 开机启动延迟 (秒)
+    #[arg(long, env = "AUTO_SCUNET_ON_BOOT_DELAY", value_name = "SECONDS")]
+    pub on_boot_delay: Option<f32>,
+
+    /// 后台模式的检查间隔 (秒)
+    #[arg(long, env = "AUTO_SCUNET_WATCH_INTERVAL", value_name = "SECONDS")]
+    pub watch_interval: Option<u64>,
+
+    /// 剩余时长低于该值时发送通知 (小时)
+    #[arg(long, env = "AUTO_SCUNET_LOW_HOUR_THRESHOLD", value_name = "HOURS")]
+    pub low_hour_threshold: Option<f64>,
+
+    /// 时长用完后切换到的服务
+    #[arg(long, env = "AUTO_SCUNET_AUTO_SWITCH_SERVICE", value_parser = parse_service)]
+    pub auto_switch_service: Option<Service>,
+
+    /// 是否启用桌面通知
+    #[arg(long, env = "AUTO_SCUNET_ENABLE_TOAST", value_name = "BOOL")]
+    pub enable_toast: Option<bool>,
+
+    /// 启用的通知渠道，用逗号分隔: desktop, stdout, file, webhook
+    #[arg(
+        long,
+        env = "AUTO_SCUNET_NOTIFICATION_SINKS",
+        value_delimiter = ',',
+        value_parser = parse_value::<NotifierKind>
+    )]
+    pub notification_sinks: Option<Vec<NotifierKind>>,
+
+    /// 日志级别: error, warn, info, debug, trace
+    #[arg(long, env = "AUTO_SCUNET_LOG_LEVEL", value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// `file` 通知渠道写入的文件
+    #[arg(long, env = "AUTO_SCUNET_NOTIFICATION_FILE", value_name = "PATH")]
+    pub notification_file: Option<String>,
+}
+
+/// 列出可以覆盖的参数和对应的配置项
+macro_rules! overridable {
+    ($($arg:ident => $($field:ident).+),* $(,)?) => {
+        impl Overrides {
+            pub fn is_empty(&self) -> bool {
+                true $(&& self.$arg.is_none())*
+            }
+
+            /// 用覆盖的值替换配置中的对应项
+            pub fn apply(&self, config: &mut AppConfig) {
+                $(if let Some(value) = &self.$arg {
+                    config.$($field).+ = value.clone().into();
+                })*
+            }
+
+            /// 把被覆盖的项恢复成配置文件中的值，避免写回配置文件
+            pub fn restore(&self, config: &mut AppConfig, file: &AppConfig) {
+                $(if self.$arg.is_some() {
+                    config.$($field).+ = file.$($field).+.clone();
+                })*
+            }
+        }
+    };
+}
+
+overridable! {
+    student_id => student_id,
+    password => password,
+    password_command => password_command,
+    password_file => password_file,
+    service => service,
+    language => language,
+    on_boot_delay => on_boot_delay,
+    watch_interval => watch_interval,
+    low_hour_threshold => low_hour_threshold,
+    auto_switch_service => auto_switch_service,
+    enable_toast => enable_toast,
+    notification_sinks => notification.sinks,
+    notification_file => notification.file,
+    log_level => log.level,
+}
+
+fn parse_service(arg: &str) -> Result<Service, String> {
+    instance::parse_service(arg).ok_or_else(|| format!("unknown service `{}`", arg))
+}
+
+/// 按配置文件中的写法解析参数
+fn parse_value<T: serde::de::DeserializeOwned>(arg: &str) -> Result<T, String> {
+    toml::Value::String(arg.into())
+        .try_into()
+        .map_err(|_| format!("invalid value `{}`", arg))
+}
@@ -1,25 +1,66 @@
-use std::sync::{Arc, LazyLock, OnceLock, RwLock};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, LazyLock, Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, sleep},
+    time::{Duration, SystemTime},
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use auto_launch::{AutoLaunch, AutoLaunchBuilder};
-use dirs::home_dir;
 use eframe::icon_data::IconDataExt;
 use egui::IconData;
 use serde::{Deserialize, Serialize};
 
 use scunet_login_util::*;
 
+use crate::{
+    cli::CLI,
+    credentials::{self, EncryptedCredentials},
+    hooks::{HOOKS, HookConfig},
+    http_api::HttpApiConfig,
+    i18n::{self, Language, t},
+    logging::{self, LogConfig},
+    metrics::MetricsConfig,
+    notification::{self, NotificationConfig, NotificationEvent, notify},
+    schedule::ScheduleRule,
+    schema::{self, CONFIG_VERSION},
+    webhook::WebhookConfig,
+};
+
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const GITHUB_REPO: &str = "https://github.com/EastMonster/auto-scunet";
 
 const CONFIG_FILE_NAME: &str = "auto-scunet.toml";
 
+/// 配置目录、数据目录和缓存目录下的子目录名
+const APP_DIR_NAME: &str = "auto-scunet";
+
+/// 便携模式下运行数据所在的目录名 (与程序同目录)
+const PORTABLE_DATA_DIR_NAME: &str = "auto-scunet-data";
+
 const WINDOWS_APP_USER_MODEL_ID: &str = "EastMonster.AutoScunet";
 
-static CONFIG_FILE: OnceLock<String> = OnceLock::new();
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// 便携模式：配置文件和运行数据都放在程序所在目录
+static PORTABLE: OnceLock<bool> = OnceLock::new();
+
+/// 本次运行是否已经备份过配置文件
+static BACKED_UP: OnceLock<()> = OnceLock::new();
+
+/// 配置文件是否成功读取过，读取失败时用的是默认配置，保存会覆盖掉用户原来的配置
+static LOADED: AtomicBool = AtomicBool::new(false);
+
+/// 最近一次保存后配置文件的修改时间，用于区分自己和其它程序的修改
+static LAST_SAVED: Mutex<Option<SystemTime>> = Mutex::new(None);
 
-pub static IS_TOAST_ENABLED: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(true));
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
 pub static ON_BOOT: OnceLock<bool> = OnceLock::new();
 
@@ -36,23 +77,69 @@ static AUTO_LAUNCH_CONF: LazyLock<AutoLaunch> = LazyLock::new(|| {
         .unwrap()
 });
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
+    /// 配置文件的版本，用于升级旧的配置
+    pub version: u32,
     // 主窗口选项
     pub student_id: String,
     pub password: String,
+    /// 登录时运行该命令，用输出的第一行作为密码
+    pub password_command: String,
+    /// 登录时读取该文件的第一行作为密码
+    pub password_file: String,
     pub service: Service,
     #[serde(skip_serializing, skip_deserializing)]
     pub on_boot: bool,
     // 设置窗口选项
     pub greeting_name: String,
+    pub language: Language,
+    /// 界面使用的字体文件，为空则自动查找
+    pub font_path: String,
     #[serde(default = "bool_true")]
     pub enable_toast: bool,
     #[serde(default = "bool_true")]
     pub show_github_button: bool,
     #[serde(default = "delay_default")]
     pub on_boot_delay: f32,
+    /// 界面缩放比例
+    #[serde(default = "ui_scale_default")]
+    pub ui_scale: f32,
+    pub theme: AppTheme,
+    /// 允许调整主窗口大小
+    pub resizable: bool,
+    /// 紧凑布局，省去标题行
+    pub compact: bool,
+    /// 上次关闭时主窗口的位置
+    pub window_pos: Option<[f32; 2]>,
+    /// 上次关闭时主窗口的大小，仅在允许调整大小时使用
+    pub window_size: Option<[f32; 2]>,
+    /// 剩余时长低于该值 (小时) 时提醒，为 0 则不提醒
+    pub low_hour_threshold: f64,
+    /// 校园网时长用完后自动切换到的运营商服务
+    pub auto_switch_service: Option<Service>,
+    /// 每月时长重置后是否自动切换回校园网
+    pub switch_back_on_reset: bool,
+    /// 后台模式下检查登录状态的间隔 (秒)
+    #[serde(default = "watch_interval_default")]
+    pub watch_interval: u64,
+    /// 用主密码加密后的学号和密码
+    pub credentials: Option<EncryptedCredentials>,
+    /// 后台模式下的本地 HTTP 控制接口
+    pub http_api: HttpApiConfig,
+    /// 后台模式下的 Prometheus 指标接口
+    pub metrics: MetricsConfig,
+    /// 日志级别和保留天数
+    pub log: LogConfig,
+    /// 登录成功、失败和注销后执行的命令
+    pub hooks: HookConfig,
+    /// 后台模式下的计划任务，为空则始终保持登录
+    pub schedule: Vec<ScheduleRule>,
+    /// 通知渠道
+    pub notification: NotificationConfig,
+    /// 登录事件的 Webhook 通知
+    pub webhooks: Vec<WebhookConfig>,
 }
 
 #[rustfmt::skip]
@@ -61,6 +148,144 @@ fn bool_true() -> bool { true }
 #[rustfmt::skip]
 fn delay_default() -> f32 { 3.0 }
 
+#[rustfmt::skip]
+fn ui_scale_default() -> f32 { 1.25 }
+
+#[rustfmt::skip]
+fn watch_interval_default() -> u64 { 60 }
+
+/// 与配置文件中省略对应项时的取值一致
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: 0,
+            student_id: String::new(),
+            password: String::new(),
+            password_command: String::new(),
+            password_file: String::new(),
+            service: Service::default(),
+            on_boot: false,
+            greeting_name: String::new(),
+            language: Language::default(),
+            font_path: String::new(),
+            enable_toast: bool_true(),
+            show_github_button: bool_true(),
+            on_boot_delay: delay_default(),
+            ui_scale: ui_scale_default(),
+            theme: AppTheme::default(),
+            resizable: false,
+            compact: false,
+            window_pos: None,
+            window_size: None,
+            low_hour_threshold: 0.0,
+            auto_switch_service: None,
+            switch_back_on_reset: false,
+            watch_interval: watch_interval_default(),
+            credentials: None,
+            http_api: HttpApiConfig::default(),
+            metrics: MetricsConfig::default(),
+            log: LogConfig::default(),
+            hooks: HookConfig::default(),
+            schedule: Vec::new(),
+            notification: NotificationConfig::default(),
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+/// 界面主题
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppTheme {
+    /// 跟随系统
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl From<AppTheme> for egui::ThemePreference {
+    fn from(theme: AppTheme) -> Self {
+        match theme {
+            AppTheme::System => egui::ThemePreference::System,
+            AppTheme::Light => egui::ThemePreference::Light,
+            AppTheme::Dark => egui::ThemePreference::Dark,
+        }
+    }
+}
+
+impl AppConfig {
+    /// 主窗口的初始大小
+    pub fn window_size(&self) -> [f32; 2] {
+        match self.window_size {
+            Some(size) if self.resizable => size,
+            _ if self.compact => [320.0, 130.0],
+            _ => [320.0, 180.0],
+        }
+    }
+}
+
+/// 存放运行数据 (如剩余时长记录) 的目录
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = if PORTABLE.get() == Some(&true) {
+        exe_dir()
+            .context("无法获取程序所在目录")?
+            .join(PORTABLE_DATA_DIR_NAME)
+    } else {
+        dirs::data_local_dir()
+            .context("无法获取数据目录")?
+            .join(APP_DIR_NAME)
+    };
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.parent()?.to_path_buf())
+}
+
+/// 决定使用的配置文件，返回路径和是否为便携模式
+///
+/// 优先级：`--config` 指定的文件 > 程序同目录下的配置文件 (或 `--portable`) > 系统配置目录
+fn resolve_config_file() -> Result<(PathBuf, bool)> {
+    if let Some(path) = &CLI.config {
+        return Ok((path.clone(), false));
+    }
+
+    // 程序放在主目录时，同目录下的就是旧版本的配置文件，不当作便携模式
+    let home_dir = dirs::home_dir();
+    if let Some(dir) = exe_dir().filter(|dir| Some(dir) != home_dir.as_ref()) {
+        let file = dir.join(CONFIG_FILE_NAME);
+        if file.exists() || CLI.portable {
+            return Ok((file, true));
+        }
+    }
+
+    let file = dirs::config_dir()
+        .context("无法获取配置目录")?
+        .join(APP_DIR_NAME)
+        .join(CONFIG_FILE_NAME);
+    if !file.exists()
+        && let Some(legacy) = home_dir.map(|dir| dir.join(CONFIG_FILE_NAME))
+        && legacy.exists()
+    {
+        migrate_config(&legacy, &file)?;
+    }
+
+    Ok((file, false))
+}
+
+/// 把旧版本放在主目录下的配置文件移动到新位置
+fn migrate_config(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to.parent().unwrap())?;
+    // 不在同一个文件系统上时无法直接重命名
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
 pub fn on_boot_change(val: bool) {
     let auto = &AUTO_LAUNCH_CONF;
 
@@ -69,7 +294,9 @@ pub fn on_boot_change(val: bool) {
 
 #[allow(unused)]
 fn init_register() -> Result<()> {
-    let icon_path = dirs::cache_dir().unwrap().join("auto-scunet.png");
+    let cache_dir = dirs::cache_dir().unwrap().join(APP_DIR_NAME);
+    std::fs::create_dir_all(&cache_dir)?;
+    let icon_path = cache_dir.join("auto-scunet.png");
     if !icon_path.exists() {
         std::fs::write(&icon_path, ICON_DATA.to_png_bytes().unwrap())?;
     }
@@ -86,29 +313,352 @@ fn init_register() -> Result<()> {
 }
 
 pub fn load_config() -> Result<AppConfig> {
+    // 之后的步骤失败时也要用到
+    ON_BOOT.set(CLI.boot).unwrap();
+
+    // 读取失败时单实例锁等也要放在便携模式的目录下
+    let (config_file, portable) = resolve_config_file()?;
+    PORTABLE.set(portable).unwrap();
+    let config_file = CONFIG_FILE.get_or_init(|| config_file);
+
     if cfg!(windows) {
         init_register()?;
     }
 
-    let args: Vec<String> = std::env::args().collect();
-    ON_BOOT.set(args.contains(&String::from("--boot"))).unwrap();
-
-    let home_dir = home_dir().unwrap();
+    let config = reload_config()?;
+    tracing::info!(version = VERSION, path = %config_file.display(), portable, "已读取配置文件");
+    Ok(config)
+}
 
-    CONFIG_FILE
-        .set(home_dir.join(CONFIG_FILE_NAME).to_str().unwrap().to_owned())
-        .unwrap();
+/// 重新读取配置文件并应用其中的通知、语言等设置
+pub fn reload_config() -> Result<AppConfig> {
+    let config_file = CONFIG_FILE.get().context("配置文件路径未初始化")?;
 
-    let mut config: AppConfig =
-        toml::from_str(&std::fs::read_to_string(CONFIG_FILE.get().unwrap())?)?;
+    // 第一次运行时还没有配置文件，按空文件处理
+    let source = match std::fs::read_to_string(config_file) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        result => result?,
+    };
+    let mut config = parse_config(&source)
+        .with_context(|| format!("{} ({})", t("配置文件有误"), config_file.display()))?;
     config.on_boot = AUTO_LAUNCH_CONF.is_enabled().unwrap();
+    // 环境变量中的主密码不对时保持锁定，界面上还可以重新输入，不能当作配置文件有误
+    if let Err(e) = credentials::open(&mut config) {
+        notify(NotificationEvent::Error {
+            message: format!(
+                "{} ({}): {:#}",
+                t("无法解锁账号"),
+                credentials::PASSPHRASE_ENV,
+                e
+            ),
+        });
+    }
 
-    *IS_TOAST_ENABLED.write().unwrap() = config.enable_toast;
+    if !CLI.overrides.is_empty() {
+        CLI.overrides.apply(&mut config);
+        let errors = schema::validate(&config, "");
+        if !errors.is_empty() {
+            anyhow::bail!("{}:\n{}", t("命令行参数或环境变量有误"), errors.join("\n"));
+        }
+    }
 
+    apply_config(&config);
+    LOADED.store(true, Ordering::Relaxed);
     Ok(config)
 }
 
+/// 让全局生效的设置立即生效
+pub fn apply_config(config: &AppConfig) {
+    logging::init(&config.log);
+    i18n::set_language(config.language);
+    notification::init(config);
+    *HOOKS.write().unwrap() = config.hooks.clone();
+}
+
+/// 在后台线程中检查配置文件，被其它程序修改后调用 `on_change`
+pub fn watch_config(on_change: impl Fn() + Send + 'static) {
+    let Some(path) = CONFIG_FILE.get() else {
+        return;
+    };
+    let modified = move || std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    thread::spawn(move || {
+        let mut last_seen = modified();
+        loop {
+            sleep(CONFIG_WATCH_INTERVAL);
+
+            let current = modified();
+            if current == last_seen {
+                continue;
+            }
+            last_seen = current;
+
+            // 自己保存引起的变化不用重新读取
+            if current.is_some() && current != *LAST_SAVED.lock().unwrap() {
+                tracing::info!("配置文件已被修改，重新读取");
+                on_change();
+            }
+        }
+    });
+}
+
+/// 合并配置文件的新内容和界面上尚未保存的修改
+///
+/// 以上次保存时的配置 `base` 为准，界面上改过的项保留界面上的值，其余项采用文件中的新值；
+/// 返回合并结果和是否保留了界面上的修改
+pub fn merge_config(
+    base: &AppConfig,
+    ours: &AppConfig,
+    theirs: &AppConfig,
+) -> Result<(AppConfig, bool)> {
+    // 窗口位置和大小随时在变，总是用界面上的值，也不算作冲突
+    const LOCAL_ONLY: [&str; 2] = ["window_pos", "window_size"];
+
+    let base_table = toml::Table::try_from(base)?;
+    let ours_table = toml::Table::try_from(ours)?;
+    let mut merged = toml::Table::try_from(theirs)?;
+    let mut kept = false;
+
+    for key in base_table.keys().chain(ours_table.keys()) {
+        if base_table.get(key) == ours_table.get(key) && !LOCAL_ONLY.contains(&key.as_str()) {
+            continue;
+        }
+        match ours_table.get(key) {
+            Some(value) => merged.insert(key.clone(), value.clone()),
+            None => merged.remove(key),
+        };
+        kept |= !LOCAL_ONLY.contains(&key.as_str());
+    }
+
+    // 是否已解锁不会写进表里，要从原来的配置中取
+    let credentials = if base.credentials == ours.credentials {
+        &theirs.credentials
+    } else {
+        &ours.credentials
+    };
+
+    let mut config: AppConfig = toml::Value::Table(merged).try_into()?;
+    config.on_boot = theirs.on_boot;
+    config.credentials = credentials.clone();
+    Ok((config, kept))
+}
+
+/// 解析、迁移并校验配置文件的内容
+pub fn parse_config(source: &str) -> Result<AppConfig> {
+    let fail = |message: String| anyhow::anyhow!(message);
+
+    // 先按原文解析一遍，类型错误才能定位到行
+    toml::from_str::<AppConfig>(source).map_err(|e| fail(schema::parse_error(source, &e)))?;
+
+    let mut table: toml::Table = toml::from_str(source)?;
+    schema::migrate(&mut table)?;
+    let config: AppConfig = toml::Value::Table(table).try_into()?;
+
+    i18n::set_language(config.language);
+    let errors = schema::validate(&config, source);
+    if !errors.is_empty() {
+        return Err(fail(errors.join("\n")));
+    }
+
+    Ok(config)
+}
+
+/// 只更新配置文件中的窗口位置和大小，界面上尚未保存的修改不会被写入
+pub fn save_window_state(pos: Option<[f32; 2]>, size: Option<[f32; 2]>) -> Result<()> {
+    let path = CONFIG_FILE.get().context("配置文件路径未初始化")?;
+    let mut config: AppConfig = toml::from_str(&std::fs::read_to_string(path)?)?;
+    config.window_pos = pos;
+    config.window_size = size;
+    save_config(&config)
+}
+
 pub fn save_config(config: &AppConfig) -> Result<()> {
-    std::fs::write(CONFIG_FILE.get().unwrap(), toml::to_string(config)?)?;
+    if !LOADED.load(Ordering::Relaxed) {
+        anyhow::bail!(t("配置文件读取失败，修复后才能保存设置"));
+    }
+    let path = CONFIG_FILE.get().context("配置文件路径未初始化")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // 第一次改写前保留一份原来的文件
+    let backup = path.with_extension("toml.bak");
+    if path.exists() && BACKED_UP.set(()).is_ok() {
+        std::fs::copy(path, &backup)?;
+    }
+    // 加密账号后备份中不能留下明文的学号和密码
+    if config.credentials.is_some() {
+        scrub_backup(&backup)?;
+    }
+    let mut config = AppConfig {
+        version: CONFIG_VERSION,
+        ..config.clone()
+    };
+    if !CLI.overrides.is_empty() {
+        let file = std::fs::read_to_string(path).unwrap_or_default();
+        let mut file = toml::from_str(&file).unwrap_or_default();
+        credentials::open(&mut file).ok();
+        CLI.overrides.restore(&mut config, &file);
+    }
+    credentials::seal(&mut config)?;
+    write_atomic(path, toml::to_string(&config)?.as_bytes())
+}
+
+/// 先写到临时文件再重命名，写到一半崩溃或磁盘已满时原文件不受影响；
+/// 写入期间锁住配置文件，避免多个实例同时写
+/// 去掉备份中明文的学号和密码
+fn scrub_backup(path: &Path) -> Result<()> {
+    let Ok(source) = std::fs::read_to_string(path) else {
+        return Ok(());
+    };
+    let Ok(mut table) = toml::from_str::<toml::Table>(&source) else {
+        return Ok(());
+    };
+
+    let removed = ["student_id", "password"]
+        .iter()
+        .filter(|key| table.remove(**key).is_some())
+        .count();
+    if removed > 0 {
+        std::fs::write(path, toml::to_string(&table)?)?;
+    }
     Ok(())
 }
+
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let lock = File::create(path.with_extension("toml.lock"))?;
+    lock.lock()?;
+
+    let temp = path.with_extension("toml.tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&temp, path)?;
+    *LAST_SAVED.lock().unwrap() = std::fs::metadata(path)?.modified().ok();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_moves_legacy_config() {
+        let dir = std::env::temp_dir().join(format!("auto-scunet-migrate-{}", std::process::id()));
+        let legacy = dir.join(CONFIG_FILE_NAME);
+        let target = dir.join("config").join(APP_DIR_NAME).join(CONFIG_FILE_NAME);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&legacy, "student_id = \"1\"\n").unwrap();
+
+        migrate_config(&legacy, &target).unwrap();
+        let moved = std::fs::read_to_string(&target);
+        let legacy_left = legacy.exists();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(moved.unwrap(), "student_id = \"1\"\n");
+        assert!(!legacy_left);
+    }
+
+    #[test]
+    fn default_matches_empty_file() {
+        let parsed: AppConfig = toml::from_str("").unwrap();
+        assert_eq!(
+            toml::to_string(&AppConfig::default()).unwrap(),
+            toml::to_string(&parsed).unwrap()
+        );
+    }
+
+    #[test]
+    fn scrub_backup_removes_plain_account() {
+        let path =
+            std::env::temp_dir().join(format!("auto-scunet-scrub-{}.toml.bak", std::process::id()));
+        std::fs::write(
+            &path,
+            "student_id = \"2024141460000\"\npassword = \"secret\"\ngreeting_name = \"张三\"\n",
+        )
+        .unwrap();
+
+        scrub_backup(&path).unwrap();
+        let scrubbed = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).ok();
+
+        let scrubbed = scrubbed.unwrap();
+        assert!(!scrubbed.contains("secret"));
+        assert!(!scrubbed.contains("2024141460000"));
+        assert!(scrubbed.contains("张三"));
+    }
+
+    #[test]
+    fn merge_takes_file_changes_without_local_edits() {
+        let base = AppConfig::default();
+        let theirs = AppConfig {
+            greeting_name: "文件".into(),
+            ..AppConfig::default()
+        };
+        let (merged, kept) = merge_config(&base, &base, &theirs).unwrap();
+        assert_eq!(merged.greeting_name, "文件");
+        assert!(!kept);
+    }
+
+    #[test]
+    fn merge_keeps_local_edits() {
+        let base = AppConfig::default();
+        let ours = AppConfig {
+            greeting_name: "界面".into(),
+            ..AppConfig::default()
+        };
+        let theirs = AppConfig {
+            font_path: "/fonts/a.ttf".into(),
+            ..AppConfig::default()
+        };
+        let (merged, kept) = merge_config(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.greeting_name, "界面");
+        assert_eq!(merged.font_path, "/fonts/a.ttf");
+        assert!(kept);
+    }
+
+    #[test]
+    fn merge_conflict_prefers_local_edit() {
+        let base = AppConfig::default();
+        let ours = AppConfig {
+            greeting_name: "界面".into(),
+            ..AppConfig::default()
+        };
+        let theirs = AppConfig {
+            greeting_name: "文件".into(),
+            ..AppConfig::default()
+        };
+        let (merged, kept) = merge_config(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.greeting_name, "界面");
+        assert!(kept);
+    }
+
+    #[test]
+    fn merge_local_only_keys_are_not_conflicts() {
+        let base = AppConfig::default();
+        let ours = AppConfig {
+            window_pos: Some([10.0, 20.0]),
+            ..AppConfig::default()
+        };
+        let theirs = AppConfig {
+            window_pos: Some([30.0, 40.0]),
+            ..AppConfig::default()
+        };
+        let (merged, kept) = merge_config(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.window_pos, Some([10.0, 20.0]));
+        assert!(!kept);
+    }
+
+    #[test]
+    fn merge_removes_option_cleared_locally() {
+        let base = AppConfig {
+            auto_switch_service: Some(Service::ChinaTelecom),
+            ..AppConfig::default()
+        };
+        let ours = AppConfig::default();
+        let (merged, kept) = merge_config(&base, &ours, &base).unwrap();
+        assert_eq!(merged.auto_switch_service, None);
+        assert!(kept);
+    }
+}
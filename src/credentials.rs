@@ -0,0 +1,242 @@
+//! 用主密码加密保存学号和密码
+//!
+//! 密钥由 Argon2id 从主密码派生，学号和密码用 ChaCha20-Poly1305 加密后保存在配置文件的 `[credentials]` 中，
+//! 明文的 `student_id` 和 `password` 则留空。界面启动时输入主密码解锁，后台模式通过环境变量提供
+
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, rand_core::RngCore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppConfig, i18n::t};
+
+/// 后台模式下提供主密码的环境变量
+pub const PASSPHRASE_ENV: &str = "AUTO_SCUNET_PASSPHRASE";
+
+/// 解锁后的密钥和派生它用的盐，保存配置时用它重新加密
+static KEY: Mutex<Option<(String, Key)>> = Mutex::new(None);
+
+/// 配置文件中加密后的账号，都以 base64 保存
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptedCredentials {
+    /// 派生密钥用的盐
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+    /// 本次运行中是否已经解锁，解锁后明文的学号和密码才有效
+    #[serde(skip)]
+    pub unlocked: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Plain {
+    student_id: String,
+    password: String,
+}
+
+/// 账号已加密但还没有解锁
+pub fn is_locked(config: &AppConfig) -> bool {
+    config.credentials.as_ref().is_some_and(|c| !c.unlocked)
+}
+
+/// 尝试用已解锁的密钥或环境变量中的主密码解锁，都没有时保持锁定
+pub fn open(config: &mut AppConfig) -> Result<()> {
+    let Some(credentials) = &config.credentials else {
+        return Ok(());
+    };
+
+    let cached = KEY.lock().unwrap().clone();
+    match cached {
+        Some((salt, key)) if salt == credentials.salt => decrypt(config, &key),
+        _ => match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => unlock(config, &passphrase),
+            Err(_) => Ok(()),
+        },
+    }
+}
+
+/// 用主密码解锁配置中的账号
+pub fn unlock(config: &mut AppConfig, passphrase: &str) -> Result<()> {
+    let Some(credentials) = &config.credentials else {
+        return Ok(());
+    };
+
+    let salt = credentials.salt.clone();
+    let key = derive_key(passphrase, &salt)?;
+    decrypt(config, &key)?;
+    *KEY.lock().unwrap() = Some((salt, key));
+    Ok(())
+}
+
+/// 开始用新的主密码加密账号，下次保存配置时生效
+pub fn enable(config: &mut AppConfig, passphrase: &str) -> Result<()> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let salt = BASE64.encode(salt);
+
+    let key = derive_key(passphrase, &salt)?;
+    *KEY.lock().unwrap() = Some((salt.clone(), key));
+    config.credentials = Some(EncryptedCredentials {
+        salt,
+        unlocked: true,
+        ..Default::default()
+    });
+    Ok(())
+}
+
+/// 取消加密，下次保存配置时学号和密码以明文保存
+pub fn disable(config: &mut AppConfig) {
+    *KEY.lock().unwrap() = None;
+    config.credentials = None;
+}
+
+/// 保存前把明文的学号和密码加密，未解锁时原样保留加密的内容
+pub fn seal(config: &mut AppConfig) -> Result<()> {
+    let Some(credentials) = &mut config.credentials else {
+        return Ok(());
+    };
+
+    let plain = Plain {
+        student_id: std::mem::take(&mut config.student_id),
+        password: std::mem::take(&mut config.password),
+    };
+    if !credentials.unlocked {
+        return Ok(());
+    }
+
+    let key = match &*KEY.lock().unwrap() {
+        Some((salt, key)) if *salt == credentials.salt => *key,
+        _ => return Err(anyhow!(t("账号加密的密钥不可用"))),
+    };
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, serde_json::to_vec(&plain)?.as_slice())
+        .map_err(|_| anyhow!(t("加密账号失败")))?;
+
+    credentials.nonce = BASE64.encode(nonce);
+    credentials.ciphertext = BASE64.encode(ciphertext);
+    Ok(())
+}
+
+fn decrypt(config: &mut AppConfig, key: &Key) -> Result<()> {
+    let Some(credentials) = &mut config.credentials else {
+        return Ok(());
+    };
+
+    let wrong = || anyhow!(t("主密码错误或加密的账号已损坏"));
+    let nonce = BASE64.decode(&credentials.nonce).map_err(|_| wrong())?;
+    let ciphertext = BASE64
+        .decode(&credentials.ciphertext)
+        .map_err(|_| wrong())?;
+    if nonce.len() != 12 {
+        return Err(wrong());
+    }
+    let plain = ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| wrong())?;
+    let plain: Plain = serde_json::from_slice(&plain).map_err(|_| wrong())?;
+
+    config.student_id = plain.student_id;
+    config.password = plain.password;
+    credentials.unlocked = true;
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &str) -> Result<Key> {
+    let salt = BASE64
+        .decode(salt)
+        .map_err(|_| anyhow!(t("主密码错误或加密的账号已损坏")))?;
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| anyhow!(e.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 各个测试共用同一个缓存的密钥，不能同时进行
+    static KEY_TEST: Mutex<()> = Mutex::new(());
+
+    fn sealed(passphrase: &str) -> AppConfig {
+        let mut config = AppConfig {
+            student_id: "2024141460000".into(),
+            password: "ilovescu!".into(),
+            ..AppConfig::default()
+        };
+        enable(&mut config, passphrase).unwrap();
+        seal(&mut config).unwrap();
+        config
+    }
+
+    /// 保存后重新读取，还没有解锁
+    fn from_file(config: &AppConfig) -> AppConfig {
+        toml::from_str(&toml::to_string(config).unwrap()).unwrap()
+    }
+
+    /// 重新启动后的状态：也没有缓存的密钥
+    fn reloaded(config: &AppConfig) -> AppConfig {
+        *KEY.lock().unwrap() = None;
+        from_file(config)
+    }
+
+    #[test]
+    fn seal_removes_plain_account() {
+        let _guard = KEY_TEST.lock().unwrap();
+        let config = sealed("passphrase");
+        assert!(config.student_id.is_empty());
+        assert!(config.password.is_empty());
+        let serialized = toml::to_string(&config).unwrap();
+        assert!(!serialized.contains("ilovescu!"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let _guard = KEY_TEST.lock().unwrap();
+        let mut config = reloaded(&sealed("passphrase"));
+        assert!(is_locked(&config));
+
+        unlock(&mut config, "passphrase").unwrap();
+        assert!(!is_locked(&config));
+        assert_eq!(config.student_id, "2024141460000");
+        assert_eq!(config.password, "ilovescu!");
+
+        // 解锁后再保存、读取时用缓存的密钥
+        seal(&mut config).unwrap();
+        let mut again = from_file(&config);
+        assert!(is_locked(&again));
+        open(&mut again).unwrap();
+        assert_eq!(again.student_id, "2024141460000");
+        assert_eq!(again.password, "ilovescu!");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let _guard = KEY_TEST.lock().unwrap();
+        let mut config = reloaded(&sealed("passphrase"));
+
+        assert!(unlock(&mut config, "wrong").is_err());
+        assert!(is_locked(&config));
+        assert!(config.password.is_empty());
+        assert!(KEY.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn locked_config_is_kept_when_saving() {
+        let _guard = KEY_TEST.lock().unwrap();
+        let original = reloaded(&sealed("passphrase"));
+        let mut config = original.clone();
+
+        seal(&mut config).unwrap();
+        assert_eq!(config.credentials, original.credentials);
+    }
+}
@@ -0,0 +1,421 @@
+//! 后台模式：不显示窗口，按计划任务登录和注销，并在掉线时重新登录
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, mpsc::RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::Local;
+use serde::{Serialize, Serializer};
+
+#[cfg(target_os = "linux")]
+use crate::dbus;
+use crate::{
+    AppConfig,
+    config::{reload_config, watch_config},
+    hooks::{self, HookEvent},
+    http_api,
+    i18n::error_text,
+    instance::{Command, Instance, service_arg},
+    logout,
+    metrics::{self, Metrics},
+    notification::{LogoutReason, NotificationEvent, notify},
+    on_login_success, password, save_config, schedule, switch,
+};
+
+use scunet_login_util::*;
+
+/// 最多保留的事件条数
+const MAX_EVENTS: usize = 100;
+
+pub type SharedDaemon = Arc<Mutex<Daemon>>;
+
+/// 登录、注销等网络操作依次进行，期间不持有后台状态的锁，查询状态不必等网络超时
+static NETWORK: Mutex<()> = Mutex::new(());
+
+/// 事件发生时的回调，调用时持有后台状态的锁，不应在其中做耗时操作
+pub type Listener = Box<dyn Fn(&Event, &DaemonStatus) + Send>;
+
+/// 后台模式下发生的事件
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    LoginSuccess,
+    LoginFailure,
+    Logout,
+    /// 检查时发现已经掉线
+    SessionLost,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Event {
+    /// Unix 时间戳 (秒)
+    pub time: i64,
+    pub kind: EventKind,
+    #[serde(serialize_with = "serialize_service")]
+    pub service: Service,
+    pub message: String,
+}
+
+/// 后台模式的运行状态
+#[derive(Debug, Serialize)]
+pub struct DaemonStatus {
+    pub online: Option<bool>,
+    #[serde(serialize_with = "serialize_optional_service")]
+    pub service: Option<Service>,
+    pub left_hour: Option<f64>,
+    pub paused: bool,
+}
+
+/// 对外的接口中服务都用命令行参数的写法
+fn serialize_service<S: Serializer>(service: &Service, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(service_arg(*service))
+}
+
+fn serialize_optional_service<S: Serializer>(
+    service: &Option<Service>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    service.map(service_arg).serialize(serializer)
+}
+
+pub struct Daemon {
+    config: AppConfig,
+    /// 上一次检查时应处于的状态，None 表示还没检查过
+    last_desired: Option<Option<Service>>,
+    /// 上一次通知过的错误，同样的错误只通知一次
+    notified_error: Option<String>,
+    /// 收到注销命令后暂停自动登录，直到收到登录命令
+    paused: bool,
+    /// 当前是否在线，None 表示还没检查过
+    online: Option<bool>,
+    /// 当前登录使用的服务
+    service: Option<Service>,
+    /// 最近一次获取到的剩余时长
+    left_hour: Option<f64>,
+    events: VecDeque<Event>,
+    listeners: Vec<Listener>,
+    metrics: Metrics,
+}
+
+pub fn run(config: AppConfig, instance: Instance) -> ! {
+    let daemon = Arc::new(Mutex::new(Daemon {
+        config,
+        last_desired: None,
+        notified_error: None,
+        paused: false,
+        online: None,
+        service: None,
+        left_hour: None,
+        events: VecDeque::new(),
+        listeners: Vec::new(),
+        metrics: Metrics::default(),
+    }));
+
+    let sender = instance.sender();
+    watch_config(move || sender.send(Command::ReloadConfig));
+
+    http_api::start(&daemon);
+    metrics::start(&daemon);
+    #[cfg(target_os = "linux")]
+    dbus::start(&daemon);
+
+    loop {
+        tick(&daemon);
+        let interval = Duration::from_secs(daemon.lock().unwrap().config.watch_interval.max(10));
+
+        match instance.commands.recv_timeout(interval) {
+            Ok(command) => handle_command(&daemon, command),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(interval),
+        }
+    }
+}
+
+impl Daemon {
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    pub fn status(&self) -> DaemonStatus {
+        DaemonStatus {
+            online: self.online,
+            service: self.service,
+            left_hour: self.left_hour,
+            paused: self.paused,
+        }
+    }
+
+    pub fn events(&self) -> &VecDeque<Event> {
+        &self.events
+    }
+
+    pub fn render_metrics(&self) -> String {
+        self.metrics.render(&self.status())
+    }
+
+    /// 注册事件回调
+    pub fn subscribe(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+
+    /// 最近一次登录失败的记录
+    pub fn last_error(&self) -> Option<&Event> {
+        self.events
+            .iter()
+            .rev()
+            .find(|e| e.kind == EventKind::LoginFailure)
+    }
+
+    /// 处理登录的结果，返回需要通知的错误 (同样的错误只通知一次)
+    fn on_login_result(
+        &mut self,
+        service: Service,
+        result: &Result<LoginStatus>,
+    ) -> Option<String> {
+        match result {
+            Ok(LoginStatus::Success(user_info)) => {
+                // 之前在线，现在却需要重新登录，说明中途掉线了
+                if self.online == Some(true) {
+                    self.online = Some(false);
+                    self.push_event(EventKind::SessionLost, service, "检测到已掉线".into());
+                    notify(NotificationEvent::SessionLost { service });
+                }
+                self.notified_error = None;
+                self.online = Some(true);
+                self.service = Some(user_info.service);
+                self.left_hour = user_info.left_hour;
+                self.push_event(
+                    EventKind::LoginSuccess,
+                    user_info.service,
+                    user_info.welcomeTip.clone(),
+                );
+                None
+            }
+            Ok(LoginStatus::HaveLoggedIn) => {
+                self.notified_error = None;
+                self.online = Some(true);
+                self.service.get_or_insert(service);
+                None
+            }
+            Err(e) => {
+                self.online = Some(false);
+                // 没连上校园网时静默等待下一次检查
+                if let Some(LoginError::NotConnectedToScunet) = e.downcast_ref::<LoginError>() {
+                    return None;
+                }
+                let msg = error_text(e);
+                self.push_event(EventKind::LoginFailure, service, msg.clone());
+                if self.notified_error.as_ref() == Some(&msg) {
+                    return None;
+                }
+                self.notified_error = Some(msg.clone());
+                Some(msg)
+            }
+        }
+    }
+
+    fn on_logged_out(&mut self) {
+        let service = self.service.take().unwrap_or(self.config.service);
+        self.online = Some(false);
+        self.push_event(EventKind::Logout, service, String::new());
+    }
+
+    fn push_event(&mut self, kind: EventKind, service: Service, message: String) {
+        let event = Event {
+            time: Local::now().timestamp(),
+            kind,
+            service,
+            message,
+        };
+
+        let status = self.status();
+        for listener in &self.listeners {
+            listener(&event, &status);
+        }
+
+        self.events.push_back(event);
+        if self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+}
+
+/// 立即登录并恢复自动登录
+pub fn login_now(daemon: &SharedDaemon) -> Result<LoginStatus> {
+    let _network = NETWORK.lock().unwrap();
+    resume_and_login(daemon)
+}
+
+/// 立即注销并暂停自动登录，直到下一次登录
+pub fn logout_now(daemon: &SharedDaemon) -> Result<LogoutStatus> {
+    let _network = NETWORK.lock().unwrap();
+    let (config, service) = {
+        let mut state = daemon.lock().unwrap();
+        state.paused = true;
+        let service = state.service.unwrap_or(state.config.service);
+        (state.config.clone(), service)
+    };
+
+    let status = logout(&config, service)?;
+    daemon.lock().unwrap().on_logged_out();
+    Ok(status)
+}
+
+/// 更换默认服务并保存，当前在线时改用新服务重新登录
+pub fn switch_service(daemon: &SharedDaemon, service: Service) -> Result<LoginStatus> {
+    let _network = NETWORK.lock().unwrap();
+    let (config, previous) = {
+        let mut state = daemon.lock().unwrap();
+        state.config.service = service;
+        save_config(&state.config)?;
+        let previous = state
+            .service
+            .filter(|s| state.online == Some(true) && *s != service);
+        (state.config.clone(), previous)
+    };
+
+    if let Some(previous) = previous {
+        logout(&config, previous)?;
+        daemon.lock().unwrap().on_logged_out();
+    }
+    resume_and_login(daemon)
+}
+
+fn resume_and_login(daemon: &SharedDaemon) -> Result<LoginStatus> {
+    let service = {
+        let mut state = daemon.lock().unwrap();
+        state.paused = false;
+        state.last_desired = None;
+        state.config.service
+    };
+    login(daemon, service)
+}
+
+fn handle_command(daemon: &SharedDaemon, command: Command) {
+    match command {
+        // 后台模式没有窗口可以显示，告诉用户为什么界面没有出来
+        Command::Show => notify(NotificationEvent::DaemonRunning),
+        Command::Login => {
+            let mut state = daemon.lock().unwrap();
+            state.paused = false;
+            // 强制重新判断一次计划任务
+            state.last_desired = None;
+        }
+        Command::Logout => match logout_now(daemon) {
+            Ok(_) => notify(NotificationEvent::LoggedOut {
+                reason: LogoutReason::Paused,
+            }),
+            Err(e) => notify(NotificationEvent::LoginFailure {
+                error: error_text(&e),
+            }),
+        },
+        // 登录的结果已经在 login 中通知过了
+        Command::SwitchService(service) => {
+            switch_service(daemon, service).ok();
+        }
+        Command::ReloadConfig => match reload_config() {
+            Ok(config) => {
+                let mut state = daemon.lock().unwrap();
+                state.config = config;
+                // 计划任务或服务可能变了，重新判断一次
+                state.last_desired = None;
+            }
+            Err(e) => notify(NotificationEvent::Error {
+                message: format!("{:#}", e),
+            }),
+        },
+    }
+}
+
+fn tick(daemon: &SharedDaemon) {
+    let _network = NETWORK.lock().unwrap();
+    let (config, last_desired, current) = {
+        let state = daemon.lock().unwrap();
+        if state.paused {
+            return;
+        }
+        let current = state.service.filter(|_| state.online == Some(true));
+        (state.config.clone(), state.last_desired, current)
+    };
+
+    let desired = if config.schedule.is_empty() {
+        Some(switch::prepare_service(&config))
+    } else {
+        schedule::desired_service(&config.schedule, Local::now().naive_local())
+    };
+
+    match desired {
+        Some(service) => {
+            // 时段切换且服务不同，需要先注销才能换服务登录
+            // 时长用完后自动切换过的，当前登录的可能已经是想要的服务
+            if let Some(Some(last)) = last_desired
+                && last != service
+                && current != Some(service)
+                && logout(&config, current.unwrap_or(last)).is_ok()
+            {
+                daemon.lock().unwrap().on_logged_out();
+            }
+            login(daemon, service).ok();
+        }
+        // 只在进入注销时段时注销一次，之后手动登录的不去管它
+        None if last_desired != Some(None) => {
+            if let Ok(LogoutStatus::Success) = logout(&config, config.service) {
+                daemon.lock().unwrap().on_logged_out();
+                notify(NotificationEvent::LoggedOut {
+                    reason: LogoutReason::Scheduled,
+                });
+            }
+        }
+        None => {}
+    }
+
+    daemon.lock().unwrap().last_desired = Some(desired);
+}
+
+/// 登录并更新后台状态，调用者应持有 [`NETWORK`]
+fn login(daemon: &SharedDaemon, service: Service) -> Result<LoginStatus> {
+    let mut config = daemon.lock().unwrap().config.clone();
+
+    let start = Instant::now();
+    let result = password::resolve(&config).and_then(|password| {
+        ScunetLoginUtil::builder()
+            .student_id(&config.student_id)
+            .password(&password)
+            .service(service)
+            .build()
+            .login()
+    });
+    let elapsed = start.elapsed();
+    password::forget_if_rejected(&result);
+    let result = result.map(|status| match status {
+        LoginStatus::Success(user_info) => LoginStatus::Success(switch::apply(&config, user_info)),
+        status => status,
+    });
+
+    let failure = {
+        let mut state = daemon.lock().unwrap();
+        state.metrics.record(service, &result, elapsed);
+        state.on_login_result(service, &result)
+    };
+
+    if let Some(error) = failure {
+        hooks::run(HookEvent::Failure {
+            error: error.clone(),
+        });
+        notify(NotificationEvent::LoginFailure { error });
+    }
+    if let Ok(LoginStatus::Success(user_info)) = &result {
+        if let Err(e) = on_login_success(&mut config, user_info.clone()) {
+            notify(NotificationEvent::Error {
+                message: format!("{:#}", e),
+            });
+        }
+        // 登录成功后保存的密码 (加密后的) 要同步回后台状态
+        daemon.lock().unwrap().config.password = config.password;
+    }
+
+    result
+}
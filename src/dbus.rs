@@ -0,0 +1,120 @@
+//! 后台模式下在会话总线上提供的 D-Bus 服务 (仅 Linux)
+//!
+//! ```sh
+//! busctl --user call io.github.EastMonster.AutoScunet /io/github/EastMonster/AutoScunet \
+//!     io.github.EastMonster.AutoScunet1 Login
+//! ```
+
+use std::{sync::mpsc::channel, thread};
+
+use blocking::unblock;
+use zbus::{blocking::connection, fdo, interface, object_server::SignalEmitter};
+
+use crate::{
+    daemon::{self, Event, SharedDaemon},
+    instance::service_arg,
+};
+
+use scunet_login_util::*;
+
+const BUS_NAME: &str = "io.github.EastMonster.AutoScunet";
+
+const OBJECT_PATH: &str = "/io/github/EastMonster/AutoScunet";
+
+struct AutoScunet {
+    daemon: SharedDaemon,
+}
+
+#[interface(name = "io.github.EastMonster.AutoScunet1")]
+impl AutoScunet {
+    /// 立即登录，返回 `success` 或 `already_logged_in`
+    ///
+    /// 登录要等网络，放到线程池中执行，期间仍可以查询状态和发出信号
+    async fn login(&self) -> fdo::Result<String> {
+        let daemon = self.daemon.clone();
+        match unblock(move || daemon::login_now(&daemon)).await {
+            Ok(LoginStatus::Success(_)) => Ok("success".into()),
+            Ok(LoginStatus::HaveLoggedIn) => Ok("already_logged_in".into()),
+            Err(e) => Err(fdo::Error::Failed(e.to_string())),
+        }
+    }
+
+    /// 注销并暂停自动登录，返回 `logged_out` 或 `not_logged_in`
+    async fn logout(&self) -> fdo::Result<String> {
+        let daemon = self.daemon.clone();
+        match unblock(move || daemon::logout_now(&daemon)).await {
+            Ok(LogoutStatus::Success) => Ok("logged_out".into()),
+            Ok(LogoutStatus::NotLoggedIn) => Ok("not_logged_in".into()),
+            Err(e) => Err(fdo::Error::Failed(e.to_string())),
+        }
+    }
+
+    /// 返回 (是否在线, 服务, 剩余时长, 是否暂停)，服务的写法与命令行参数相同，剩余时长未知时为 -1
+    fn status(&self) -> (bool, String, f64, bool) {
+        let status = self.daemon.lock().unwrap().status();
+        (
+            status.online.unwrap_or(false),
+            status
+                .service
+                .map(|s| service_arg(s).to_owned())
+                .unwrap_or_default(),
+            status.left_hour.unwrap_or(-1.0),
+            status.paused,
+        )
+    }
+
+    /// 登录、注销、掉线等状态变化时发出
+    #[zbus(signal)]
+    async fn state_changed(
+        emitter: &SignalEmitter<'_>,
+        online: bool,
+        event: &str,
+        message: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// 在会话总线上注册服务，没有会话总线 (如无桌面的服务器) 时只记录日志
+pub fn start(daemon: &SharedDaemon) {
+    let iface = AutoScunet {
+        daemon: daemon.clone(),
+    };
+
+    let conn = match connection::Builder::session()
+        .and_then(|b| b.name(BUS_NAME))
+        .and_then(|b| b.serve_at(OBJECT_PATH, iface))
+        .and_then(|b| b.build())
+    {
+        Ok(conn) => conn,
+        Err(e) => {
+            tracing::warn!(error = %e, "D-Bus 服务启动失败");
+            return;
+        }
+    };
+
+    // 回调里持有后台状态的锁，信号交给单独的线程发送
+    let (tx, rx) = channel::<(bool, Event)>();
+    daemon
+        .lock()
+        .unwrap()
+        .subscribe(Box::new(move |event, status| {
+            tx.send((status.online.unwrap_or(false), event.clone()))
+                .ok();
+        }));
+
+    thread::spawn(move || {
+        let Ok(iface) = conn.object_server().interface::<_, AutoScunet>(OBJECT_PATH) else {
+            return;
+        };
+
+        for (online, event) in rx {
+            let kind = serde_json::to_value(event.kind).unwrap_or_default();
+            zbus::block_on(AutoScunet::state_changed(
+                iface.signal_emitter(),
+                online,
+                kind.as_str().unwrap_or_default(),
+                &event.message,
+            ))
+            .ok();
+        }
+    });
+}
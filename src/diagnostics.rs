@@ -0,0 +1,255 @@
+//! 记录最近一次登录的每一步耗时和结果，用于诊断窗口
+//!
+//! 登录库为每一步创建同名的 span (见 `scunet_login_util`)，这里在 span 结束时记下耗时，
+//! 并把 span 内的警告当作这一步失败的原因
+
+use std::{
+    fmt::Write as _,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{
+    config::VERSION,
+    i18n::{t, tf},
+    logging,
+};
+
+/// 登录库中表示一次登录的 span
+const LOGIN_SPAN: &str = "login";
+
+/// 登录的各个步骤，按执行顺序排列
+static STEPS: [(&str, &str); 6] = [
+    ("wifi_check", "网络检查"),
+    ("portal_probe", "访问认证页面"),
+    ("page_info", "获取 pageInfo"),
+    ("encrypt", "加密密码"),
+    ("login_post", "提交登录"),
+    ("user_info", "获取用户信息"),
+];
+
+static LAST_LOGIN: Mutex<Option<LoginAttempt>> = Mutex::new(None);
+
+#[derive(Clone, Debug)]
+pub struct LoginAttempt {
+    pub time: DateTime<Local>,
+    pub service: String,
+    pub steps: Vec<Step>,
+    /// 整个登录的耗时，还在进行时为 `None`
+    pub total: Option<Duration>,
+    /// 登录过程中最后一条日志，通常就是登录的结果
+    pub outcome: String,
+    pub failed: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub name: &'static str,
+    pub duration: Duration,
+    /// 失败的原因
+    pub error: Option<String>,
+}
+
+impl Step {
+    /// 当前语言下的步骤名称
+    pub fn label(&self) -> &'static str {
+        STEPS
+            .iter()
+            .find(|(name, _)| *name == self.name)
+            .map_or(self.name, |(_, label)| t(label))
+    }
+}
+
+pub fn last_login() -> Option<LoginAttempt> {
+    LAST_LOGIN.lock().unwrap().clone()
+}
+
+/// 复制给开发者看的诊断信息：版本、最近一次登录和最近的日志
+pub fn report() -> String {
+    let mut out = format!(
+        "AutoSCUNET v{} ({} {})\n\n",
+        VERSION,
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    match last_login() {
+        Some(attempt) => {
+            let total = attempt
+                .total
+                .map_or_else(|| t("进行中").to_string(), format_duration);
+            writeln!(
+                out,
+                "{} {} [{}] {}",
+                t("最近一次登录"),
+                attempt.time.format("%Y-%m-%d %H:%M:%S"),
+                attempt.service,
+                total
+            )
+            .ok();
+            for step in &attempt.steps {
+                let result = step.error.as_deref().unwrap_or("ok");
+                writeln!(
+                    out,
+                    "  {:<16} {:>8}  {}",
+                    step.name,
+                    format_duration(step.duration),
+                    result
+                )
+                .ok();
+            }
+            writeln!(out, "  => {}", attempt.outcome).ok();
+        }
+        None => {
+            writeln!(out, "{}", t("还没有登录记录")).ok();
+        }
+    }
+
+    out.push('\n');
+    for line in logging::recent_lines() {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    tf("{} ms", &[&duration.as_millis()])
+}
+
+/// 收集登录步骤耗时的 `tracing` 层
+pub struct DiagnosticsLayer;
+
+/// 记在 span 上的开始时间和失败原因
+struct Timing {
+    start: Instant,
+    error: Option<String>,
+}
+
+impl<S> Layer<S> for DiagnosticsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let name = attrs.metadata().name();
+        if name != LOGIN_SPAN && !is_step(name) {
+            return;
+        }
+
+        // 运营商登录失败回退到校园网时会嵌套一次登录，算作同一次
+        let nested = span.scope().skip(1).any(|s| s.name() == LOGIN_SPAN);
+        // 注销等操作也会探测网络，不在登录中的步骤不计入
+        if is_step(name) && !nested {
+            return;
+        }
+        if name == LOGIN_SPAN && !nested {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            *LAST_LOGIN.lock().unwrap() = Some(LoginAttempt {
+                time: Local::now(),
+                service: fields.service.unwrap_or_default(),
+                steps: Vec::new(),
+                total: None,
+                outcome: String::new(),
+                failed: false,
+            });
+        }
+
+        span.extensions_mut().insert(Timing {
+            start: Instant::now(),
+            error: None,
+        });
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        if span.scope().all(|s| s.name() != LOGIN_SPAN) {
+            return;
+        }
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let message = match (fields.message, fields.error) {
+            (Some(message), Some(error)) => format!("{}: {}", message, error),
+            (message, error) => message.or(error).unwrap_or_default(),
+        };
+        let message = logging::redact(&message);
+        let failed = *event.metadata().level() <= Level::WARN;
+
+        if failed
+            && is_step(span.name())
+            && let Some(timing) = span.extensions_mut().get_mut::<Timing>()
+        {
+            timing.error = Some(message.clone());
+        }
+        if let Some(attempt) = LAST_LOGIN.lock().unwrap().as_mut() {
+            attempt.outcome = message;
+            attempt.failed = failed;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let name = span.name();
+        let Some(timing) = span.extensions_mut().remove::<Timing>() else {
+            return;
+        };
+        let mut last = LAST_LOGIN.lock().unwrap();
+        let Some(attempt) = last.as_mut() else {
+            return;
+        };
+
+        if is_step(name) {
+            attempt.steps.push(Step {
+                name,
+                duration: timing.start.elapsed(),
+                error: timing.error,
+            });
+        } else if span.scope().skip(1).all(|s| s.name() != LOGIN_SPAN) {
+            attempt.total = Some(timing.start.elapsed());
+        }
+    }
+}
+
+fn is_step(name: &str) -> bool {
+    STEPS.iter().any(|(step, _)| *step == name)
+}
+
+/// 从 span 和日志中取出需要的字段
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    error: Option<String>,
+    service: Option<String>,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = Some(value.to_string());
+        match field.name() {
+            "message" => self.message = value,
+            "error" => self.error = value,
+            "service" => self.service = value,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! 查找界面使用的中文字体
+//!
+//! 依次尝试配置中的 `font_path`、fontconfig 匹配结果和各系统的常见路径
+
+use std::{fs::read, path::Path};
+
+#[cfg(windows)]
+const FONT_PATHS: &[&str] = &["C:/Windows/Fonts/msyh.ttc", "C:/Windows/Fonts/simsun.ttc"];
+
+#[cfg(target_os = "macos")]
+const FONT_PATHS: &[&str] = &[
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+];
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc", // Arch Linux: pacman -S noto-fonts-cjk
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc", // Ubuntu 22.04: apt install fonts-noto-cjk
+    "/usr/share/fonts/google-noto-sans-cjk-fonts/NotoSansCJK-Regular.ttc", // Fedora
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wqy-microhei/wqy-microhei.ttc",
+];
+
+/// 找到的字体文件内容，以及在字体集合中的序号
+pub struct FontFile {
+    pub data: Vec<u8>,
+    pub index: u32,
+}
+
+/// 查找可用的中文字体，都找不到时返回 `None`
+pub fn find_cjk_font(font_path: &str) -> Option<FontFile> {
+    if !font_path.is_empty()
+        && let Some(font) = load(Path::new(font_path), 0)
+    {
+        return Some(font);
+    }
+
+    query_fontconfig().or_else(|| FONT_PATHS.iter().find_map(|path| load(Path::new(path), 0)))
+}
+
+/// 读取字体文件，并确认它看起来确实是 TrueType / OpenType 字体
+///
+/// egui 遇到无法解析的字体会直接 panic，所以先检查一下文件头
+fn load(path: &Path, index: u32) -> Option<FontFile> {
+    let data = read(path).ok()?;
+    let magic = data.get(..4)?;
+    let is_font = matches!(magic, [0, 1, 0, 0] | b"OTTO" | b"ttcf" | b"true");
+    is_font.then_some(FontFile { data, index })
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn query_fontconfig() -> Option<FontFile> {
+    let output = std::process::Command::new("fc-match")
+        .args(["-f", "%{file}\n%{index}\n%{lang}", ":lang=zh-cn"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    let mut lines = output.lines();
+    let (file, index, langs) = (lines.next()?, lines.next()?, lines.next()?);
+
+    // 没有中文字体时 fontconfig 也会返回一个最接近的字体
+    if !langs.split('|').any(|lang| lang == "zh-cn") {
+        return None;
+    }
+    load(Path::new(file), index.parse().unwrap_or(0))
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn query_fontconfig() -> Option<FontFile> {
+    None
+}
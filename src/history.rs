@@ -0,0 +1,170 @@
+//! 剩余时长记录，用于绘制趋势图和估算免费时长的耗尽时间
+
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppConfig,
+    config::data_dir,
+    notification::{NotificationEvent, notify},
+};
+
+const HISTORY_FILE_NAME: &str = "left-hour.toml";
+
+/// 最多保留的记录条数
+const MAX_SAMPLES: usize = 500;
+
+/// 一次登录时获取到的剩余时长
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Sample {
+    /// Unix 时间戳 (秒)
+    pub time: i64,
+    pub left_hour: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeftHourHistory {
+    /// 因校园网时长用完而自动切换到运营商服务的时间
+    pub switched_at: Option<i64>,
+    pub samples: Vec<Sample>,
+}
+
+impl LeftHourHistory {
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.last()
+    }
+
+    /// 当前计费周期内的记录
+    ///
+    /// 剩余时长只会减少，一旦比上一条记录多了就说明已经重置过了
+    pub fn current_cycle(&self) -> &[Sample] {
+        let start = self
+            .samples
+            .windows(2)
+            .rposition(|w| w[1].left_hour > w[0].left_hour)
+            .map_or(0, |i| i + 1);
+
+        &self.samples[start..]
+    }
+
+    /// 按当前周期的平均消耗速度估算剩余时长的耗尽时间
+    pub fn estimate_exhaustion(&self) -> Option<DateTime<Local>> {
+        let cycle = self.current_cycle();
+        let (first, last) = (cycle.first()?, cycle.last()?);
+
+        if last.left_hour <= 0.0 {
+            return Local.timestamp_opt(last.time, 0).single();
+        }
+
+        let used = first.left_hour - last.left_hour;
+        let elapsed = (last.time - first.time) as f64;
+        if used <= 0.0 || elapsed <= 0.0 {
+            return None;
+        }
+
+        let secs_left = last.left_hour / used * elapsed;
+        Local
+            .timestamp_opt(last.time + secs_left as i64, 0)
+            .single()
+    }
+
+    fn push(&mut self, left_hour: f64) {
+        self.samples.push(Sample {
+            time: Local::now().timestamp(),
+            left_hour,
+        });
+
+        if self.samples.len() > MAX_SAMPLES {
+            let excess = self.samples.len() - MAX_SAMPLES;
+            self.samples.drain(..excess);
+        }
+    }
+}
+
+pub fn load_history() -> Result<LeftHourHistory> {
+    let path = data_dir()?.join(HISTORY_FILE_NAME);
+    Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+pub fn save_history(history: &LeftHourHistory) -> Result<()> {
+    std::fs::write(
+        data_dir()?.join(HISTORY_FILE_NAME),
+        toml::to_string(history)?,
+    )?;
+    Ok(())
+}
+
+/// 记录本次登录获取到的剩余时长，低于阈值时发出提醒
+pub fn record_left_hour(left_hour: f64, config: &AppConfig) -> Result<()> {
+    let mut history = load_history().unwrap_or_default();
+    history.push(left_hour);
+    save_history(&history)?;
+
+    if config.low_hour_threshold > 0.0 && left_hour < config.low_hour_threshold {
+        notify(NotificationEvent::LowHour {
+            left_hour,
+            exhaustion: history.estimate_exhaustion(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(samples: &[(i64, f64)]) -> LeftHourHistory {
+        LeftHourHistory {
+            switched_at: None,
+            samples: samples
+                .iter()
+                .map(|&(time, left_hour)| Sample { time, left_hour })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn current_cycle_starts_after_reset() {
+        let history = history(&[(0, 10.0), (100, 5.0), (200, 40.0), (300, 38.0)]);
+        let cycle = history.current_cycle();
+        assert_eq!(cycle.len(), 2);
+        assert_eq!(cycle[0].left_hour, 40.0);
+    }
+
+    #[test]
+    fn estimate_by_average_usage() {
+        // 每 1000 秒用掉 1 小时，还剩 2 小时
+        let history = history(&[(1000, 4.0), (2000, 3.0), (3000, 2.0)]);
+        let expected = Local.timestamp_opt(5000, 0).single();
+        assert_eq!(history.estimate_exhaustion(), expected);
+    }
+
+    #[test]
+    fn estimate_ignores_previous_cycle() {
+        let history = history(&[(0, 1.0), (1000, 0.0), (2000, 10.0), (3000, 9.0)]);
+        let expected = Local.timestamp_opt(3000 + 9 * 1000, 0).single();
+        assert_eq!(history.estimate_exhaustion(), expected);
+    }
+
+    #[test]
+    fn already_exhausted() {
+        let history = history(&[(1000, 1.0), (2000, 0.0)]);
+        assert_eq!(
+            history.estimate_exhaustion(),
+            Local.timestamp_opt(2000, 0).single()
+        );
+    }
+
+    #[test]
+    fn no_estimate_without_usage() {
+        assert_eq!(history(&[]).estimate_exhaustion(), None);
+        assert_eq!(history(&[(1000, 5.0)]).estimate_exhaustion(), None);
+        assert_eq!(
+            history(&[(1000, 5.0), (2000, 5.0)]).estimate_exhaustion(),
+            None
+        );
+    }
+}
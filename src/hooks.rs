@@ -0,0 +1,224 @@
+//! 登录成功、失败和注销后执行的用户命令
+//!
+//! 事件信息通过环境变量传给命令：`AUTO_SCUNET_EVENT` `AUTO_SCUNET_USER_NAME`
+//! `AUTO_SCUNET_SERVICE` `AUTO_SCUNET_LEFT_HOUR` `AUTO_SCUNET_ERROR`，
+//! 其中服务的写法与命令行参数相同，如 `china_mobile`。命令的输出会追加到数据目录下的 `hooks.log` 中
+
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    process::{Child, Command, Stdio},
+    sync::{LazyLock, Mutex, RwLock},
+    thread::{self, JoinHandle, sleep},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::data_dir, instance::service_arg, join_until};
+
+use scunet_login_util::Service;
+
+pub static HOOKS: LazyLock<RwLock<HookConfig>> = LazyLock::new(Default::default);
+
+const HOOK_LOG_FILE_NAME: &str = "hooks.log";
+
+/// 还在执行的命令，程序退出前要等它们结束
+static RUNNING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// 各事件执行的命令，为空则不执行
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookConfig {
+    pub on_success: String,
+    pub on_failure: String,
+    pub on_logout: String,
+    /// 命令的超时时间 (秒)，超时后会被结束
+    pub timeout: u64,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        Self {
+            on_success: String::new(),
+            on_failure: String::new(),
+            on_logout: String::new(),
+            timeout: 30,
+        }
+    }
+}
+
+/// 会执行命令的事件
+#[derive(Clone, Debug)]
+pub enum HookEvent {
+    Success {
+        name: String,
+        service: Service,
+        left_hour: Option<f64>,
+    },
+    Failure {
+        error: String,
+    },
+    Logout {
+        service: Service,
+    },
+}
+
+impl HookEvent {
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![];
+        match self {
+            HookEvent::Success {
+                name,
+                service,
+                left_hour,
+            } => {
+                env.push(("AUTO_SCUNET_EVENT", "success".into()));
+                env.push(("AUTO_SCUNET_USER_NAME", name.clone()));
+                env.push(("AUTO_SCUNET_SERVICE", service_arg(*service).into()));
+                if let Some(h) = left_hour {
+                    env.push(("AUTO_SCUNET_LEFT_HOUR", h.to_string()));
+                }
+            }
+            HookEvent::Failure { error } => {
+                env.push(("AUTO_SCUNET_EVENT", "failure".into()));
+                env.push(("AUTO_SCUNET_ERROR", error.clone()));
+            }
+            HookEvent::Logout { service } => {
+                env.push(("AUTO_SCUNET_EVENT", "logout".into()));
+                env.push(("AUTO_SCUNET_SERVICE", service_arg(*service).into()));
+            }
+        }
+        env
+    }
+}
+
+/// 在后台线程中执行该事件对应的命令，退出前需调用 [`wait`]
+pub fn run(event: HookEvent) {
+    let config = HOOKS.read().unwrap().clone();
+    let command = match event {
+        HookEvent::Success { .. } => config.on_success,
+        HookEvent::Failure { .. } => config.on_failure,
+        HookEvent::Logout { .. } => config.on_logout,
+    };
+
+    if command.trim().is_empty() {
+        return;
+    }
+
+    let timeout = Duration::from_secs(config.timeout.max(1));
+    let handle = thread::spawn(move || {
+        let result = execute(&command, &event, timeout);
+        match &result {
+            Ok((status, _)) => tracing::info!(status, "钩子命令已执行"),
+            Err(e) => tracing::warn!(error = %e, "钩子命令无法执行"),
+        }
+        write_log(&command, &result).ok();
+    });
+
+    let mut running = RUNNING.lock().unwrap();
+    running.retain(|handle| !handle.is_finished());
+    running.push(handle);
+}
+
+/// 等待还在执行的命令，超时的命令会被结束，最多多等一秒写日志
+pub fn wait() {
+    let timeout = Duration::from_secs(HOOKS.read().unwrap().timeout.max(1));
+    let running = std::mem::take(&mut *RUNNING.lock().unwrap());
+    join_until(running, Instant::now() + timeout + Duration::from_secs(1));
+}
+
+/// 执行结果：退出状态描述和输出
+type HookOutput = (String, String);
+
+fn execute(command: &str, event: &HookEvent, timeout: Duration) -> Result<HookOutput> {
+    let mut child = shell(command)
+        .envs(event.env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // 输出太多时不读出来会把子进程卡住
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let Some(status) = wait_with_timeout(&mut child, timeout)? else {
+        // 命令启动的子进程可能还拿着管道，不再等它的输出
+        return Ok((
+            format!("超时 ({} 秒) 被结束", timeout.as_secs()),
+            String::new(),
+        ));
+    };
+
+    let mut output = stdout.join().unwrap_or_default();
+    output.push_str(&stderr.join().unwrap_or_default());
+
+    Ok((status, output))
+}
+
+pub fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]).creation_flags(CREATE_NO_WINDOW);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buf = String::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_string(&mut buf).ok();
+        }
+        buf
+    })
+}
+
+/// 等待命令结束并返回退出状态，超时则结束命令并返回 `None`
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<Option<String>> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status.to_string()));
+        }
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        sleep(Duration::from_millis(100));
+    }
+}
+
+fn write_log(command: &str, result: &Result<HookOutput>) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(data_dir()?.join(HOOK_LOG_FILE_NAME))?;
+
+    let time = Local::now().format("%Y-%m-%d %H:%M:%S");
+    match result {
+        Ok((status, output)) => {
+            writeln!(file, "[{}] {} ({})", time, command, status)?;
+            if !output.is_empty() {
+                writeln!(file, "{}", output.trim_end())?;
+            }
+        }
+        Err(e) => writeln!(file, "[{}] {} (无法执行: {})", time, command, e)?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! 后台模式下的本地 HTTP 控制接口
+//!
+//! 所有接口都需要在 `Authorization: Bearer <token>` 头或 `?token=` 参数中带上配置的 token，
+//! 为了能做成浏览器书签，操作类接口也接受 GET 请求。服务的写法与命令行参数相同，如 `china_mobile`
+//!
+//! | 路径 | 说明 |
+//! | --- | --- |
+//! | `/status` | 当前状态 |
+//! | `/login` | 立即登录 |
+//! | `/logout` | 注销并暂停自动登录 |
+//! | `/service?name=china_mobile` | 更换服务并重新登录 |
+//! | `/last-error` | 最近一次登录失败的记录 |
+//! | `/history` | 最近的事件与剩余时长记录 |
+
+use std::{io::Cursor, thread};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{
+    daemon::{self, SharedDaemon},
+    history::load_history,
+    i18n::{t, tf},
+    instance::{parse_service, service_arg, token_eq},
+    notification::{NotificationEvent, notify},
+};
+
+use scunet_login_util::*;
+
+/// HTTP 控制接口的配置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpApiConfig {
+    pub enable: bool,
+    /// 监听地址，默认只允许本机访问
+    pub listen: String,
+    /// 访问接口所需的 token，为空时不会启动
+    pub token: String,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            listen: "127.0.0.1:47136".into(),
+            token: String::new(),
+        }
+    }
+}
+
+/// 按配置在后台线程中启动 HTTP 接口
+pub fn start(daemon: &SharedDaemon) {
+    let config = daemon.lock().unwrap().config().http_api.clone();
+    if !config.enable {
+        return;
+    }
+    if config.token.is_empty() {
+        notify(NotificationEvent::Error {
+            message: t("HTTP 接口未启动: 请先在配置文件中设置 token").into(),
+        });
+        return;
+    }
+
+    let server = match Server::http(&config.listen) {
+        Ok(server) => server,
+        Err(e) => {
+            notify(NotificationEvent::Error {
+                message: tf("HTTP 接口启动失败: {}", &[&e]),
+            });
+            return;
+        }
+    };
+
+    let daemon = daemon.clone();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            // 每个请求单独一个线程，登录时排队等网络的请求不会挡住查询状态
+            let (daemon, token) = (daemon.clone(), config.token.clone());
+            thread::spawn(move || {
+                let (code, body) = if is_authorized(&request, &token) {
+                    handle(&request, &daemon)
+                } else {
+                    (401, json!({ "error": "unauthorized" }))
+                };
+                request.respond(json_response(code, &body)).ok();
+            });
+        }
+    });
+}
+
+fn handle(request: &Request, daemon: &SharedDaemon) -> (u16, Value) {
+    let (path, query) = split_url(request.url());
+
+    // 登录、注销和更换服务会排队等待其它网络操作，查询类的请求只短暂持有后台状态的锁
+    match path {
+        "/status" => (200, json!(daemon.lock().unwrap().status())),
+        "/login" => login_response(daemon::login_now(daemon)),
+        "/logout" => match daemon::logout_now(daemon) {
+            Ok(LogoutStatus::Success) => (200, json!({ "status": "logged_out" })),
+            Ok(LogoutStatus::NotLoggedIn) => (200, json!({ "status": "not_logged_in" })),
+            Err(e) => (502, json!({ "error": e.to_string() })),
+        },
+        "/service" => match query_param(query, "name").and_then(|name| parse_service(&name)) {
+            Some(service) => login_response(daemon::switch_service(daemon, service)),
+            None => (400, json!({ "error": "unknown service" })),
+        },
+        "/last-error" => (200, json!(daemon.lock().unwrap().last_error())),
+        "/history" => (
+            200,
+            json!({
+                "events": daemon.lock().unwrap().events(),
+                "left_hour": load_history().unwrap_or_default().samples,
+            }),
+        ),
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+fn login_response(result: anyhow::Result<LoginStatus>) -> (u16, Value) {
+    match result {
+        Ok(LoginStatus::Success(user_info)) => (
+            200,
+            json!({
+                "status": "success",
+                "service": service_arg(user_info.service),
+                "left_hour": user_info.left_hour,
+            }),
+        ),
+        Ok(LoginStatus::HaveLoggedIn) => (200, json!({ "status": "already_logged_in" })),
+        Err(e) => (502, json!({ "error": e.to_string() })),
+    }
+}
+
+fn is_authorized(request: &Request, token: &str) -> bool {
+    let from_header = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|t| token_eq(t, token));
+
+    let (_, query) = split_url(request.url());
+    from_header || query_param(query, "token").is_some_and(|t| token_eq(&t, token))
+}
+
+fn split_url(url: &str) -> (&str, &str) {
+    url.split_once('?').unwrap_or((url, ""))
+}
+
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| percent_decode(v))
+}
+
+/// 解码 URL 中的 `%XX` 和 `+`，无效的转义原样保留
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                out.push(byte);
+                i += 2;
+            }
+            (None, b'+') => out.push(b' '),
+            (None, byte) => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn json_response(code: u16, body: &Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(body.to_string())
+        .with_status_code(code)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
@@ -0,0 +1,289 @@
+//! 界面、错误信息和通知的多语言支持
+//!
+//! 代码中直接写中文原文，通过 [`t`] 查表得到当前语言的译文，没有译文时原样显示
+
+use std::{cell::Cell, fmt::Display, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use scunet_login_util::{LoginError, Service};
+
+static LANGUAGE: RwLock<Language> = RwLock::new(Language::Chinese);
+
+thread_local! {
+    /// 只对当前线程生效的语言，没有中文字体时界面线程改用英文
+    static THREAD_LANGUAGE: Cell<Option<Language>> = const { Cell::new(None) };
+}
+
+/// 界面语言
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "zh-CN")]
+    Chinese,
+    #[serde(rename = "en")]
+    English,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::Chinese, Language::English];
+
+    /// 语言自身的名称，不随界面语言变化
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Chinese => "简体中文",
+            Language::English => "English",
+        }
+    }
+}
+
+pub fn language() -> Language {
+    THREAD_LANGUAGE
+        .get()
+        .unwrap_or_else(|| *LANGUAGE.read().unwrap())
+}
+
+pub fn set_language(language: Language) {
+    *LANGUAGE.write().unwrap() = language;
+}
+
+/// 让当前线程固定使用某种语言，不影响其它线程和通知
+pub fn set_thread_language(language: Option<Language>) {
+    THREAD_LANGUAGE.set(language);
+}
+
+/// 忽略当前线程固定的语言，按配置的语言执行 `f`
+pub fn with_configured_language<T>(f: impl FnOnce() -> T) -> T {
+    let language = THREAD_LANGUAGE.replace(None);
+    let result = f();
+    THREAD_LANGUAGE.set(language);
+    result
+}
+
+/// 翻译一段中文原文
+pub fn t(text: &str) -> &str {
+    match language() {
+        Language::Chinese => text,
+        Language::English => english(text).unwrap_or(text),
+    }
+}
+
+/// 翻译带 `{}` 占位符的原文，再依次填入参数
+pub fn tf(text: &str, args: &[&dyn Display]) -> String {
+    let mut out = t(text).to_string();
+    for arg in args {
+        if let Some(pos) = out.find("{}") {
+            out.replace_range(pos..pos + 2, &arg.to_string());
+        }
+    }
+    out
+}
+
+/// 当前语言下的服务名称
+pub fn service_name(service: Service) -> &'static str {
+    t(service.to_str())
+}
+
+/// 当前语言下的错误信息，登录接口返回的信息无法翻译时原样显示
+pub fn error_text(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<LoginError>() {
+        Some(LoginError::Fail(message)) => t(message).to_string(),
+        Some(LoginError::TimeOut) => t("连接超时").to_string(),
+        Some(LoginError::WiFiStatusError(message, code)) => tf("错误 {}: {}", &[code, &t(message)]),
+        Some(LoginError::NotConnectedToScunet) => t("未连接到 SCUNET").to_string(),
+        None => e.to_string(),
+    }
+}
+
+#[rustfmt::skip]
+fn english(text: &str) -> Option<&'static str> {
+    let translated = match text {
+        // 服务
+        "校园网" => "Campus network",
+        "中国移动" => "China Mobile",
+        "中国电信" => "China Telecom",
+        "中国联通" => "China Unicom",
+
+        // 主界面
+        "登录到 SCUNET" => "Log in to SCUNET",
+        "查看 GitHub 仓库" => "View GitHub repository",
+        "设置" => "Settings",
+        "学号:" => "Student ID:",
+        "密码:" => "Password:",
+        "登录时从外部来源读取" => "Read from an external source when logging in",
+        "账号已加密，请输入主密码解锁" => "Your account is encrypted, enter the passphrase to unlock",
+        "主密码:" => "Passphrase:",
+        "解锁" => "Unlock",
+        "开机启动" => "Launch on boot",
+        "登录" => "Log in",
+        "你目前已登录到 SCUNET!" => "You are already logged in to SCUNET!",
+        "你目前未连接到 SCUNET!" => "You are not connected to SCUNET!",
+        "配置已更新" => "Settings updated",
+        "已注销" => "Logged out",
+        "你目前未登录" => "You are not logged in",
+        "正在登录..." => "Logging in...",
+        "正在切换服务..." => "Switching service...",
+        "正在注销..." => "Logging out...",
+        "已重新加载配置文件" => "Config file reloaded",
+        "配置文件已更新，界面上未保存的修改已保留" => "Config file changed, your unsaved edits were kept",
+
+        // 设置
+        "语言" => "Language",
+        "问候称呼" => "Greeting name",
+        "留空则使用真实姓名" => "Leave empty to use your real name",
+        "开机启动延迟" => "Boot delay",
+        " 秒" => " s",
+        " 小时" => " h",
+        "低时长提醒" => "Low time alert",
+        "剩余时长低于该值时发送通知，设为 0 则不提醒" => "Notify when the remaining time drops below this value, 0 to disable",
+        "趋势" => "Trend",
+        "时长用完后切换到" => "When time runs out, switch to",
+        "不切换" => "Don't switch",
+        "每月重置后切换回校园网" => "Switch back to campus network after the monthly reset",
+        "计划任务" => "Schedule",
+        "仅在以 --daemon 后台模式运行时生效" => "Only takes effect in --daemon mode",
+        "启用通知" => "Enable notifications",
+        "显示 GitHub 按钮" => "Show GitHub button",
+        "界面缩放" => "UI scale",
+        "主题" => "Theme",
+        "跟随系统" => "System",
+        "浅色" => "Light",
+        "深色" => "Dark",
+        "可调整窗口大小" => "Resizable window",
+        "紧凑布局" => "Compact layout",
+
+        // 剩余时长
+        "剩余时长" => "Remaining time",
+        "暂无记录，登录校园网后会自动记录" => "No records yet, they are collected after logging in to the campus network",
+        "当前剩余: {} 小时" => "Remaining: {} h",
+        "预计耗尽: {}" => "Expected to run out: {}",
+        "预计耗尽: 暂无法估算" => "Expected to run out: not enough data",
+        "记录不足" => "Not enough records",
+        "{} 小时" => "{} h",
+
+        // 计划任务
+        "一" => "Mon",
+        "二" => "Tue",
+        "三" => "Wed",
+        "四" => "Thu",
+        "五" => "Fri",
+        "六" => "Sat",
+        "日" => "Sun",
+        "不选则每天生效" => "Applies every day if none is selected",
+        "删除" => "Delete",
+        "时间格式应为 HH:MM" => "Time should be in HH:MM format",
+        "添加时段" => "Add period",
+        "检查间隔" => "Check interval",
+
+        // 导入导出
+        "导入/导出配置..." => "Import/export settings...",
+        "导入/导出配置" => "Import/export settings",
+        "文件" => "File",
+        "导出时不包含账号、token、Webhook 和钩子命令" => "Leave out the account, tokens, webhooks and hook commands when exporting",
+        "导出" => "Export",
+        "导入" => "Import",
+        "导入后会覆盖当前的设置" => "Importing replaces your current settings",
+        "配置已导出到 {}" => "Settings exported to {}",
+        "已从 {} 导入配置" => "Settings imported from {}",
+        "导出的配置" => "exported settings",
+        "导出配置失败" => "Failed to export settings",
+        "导入配置失败" => "Failed to import settings",
+
+        // 账号加密
+        "账号加密" => "Account encryption",
+        "学号和密码已用主密码加密保存" => "Student ID and password are encrypted with a passphrase",
+        "取消加密" => "Disable encryption",
+        "启用加密" => "Enable encryption",
+        "忘记主密码后需要重新填写学号和密码" => "If you forget the passphrase you'll have to enter your student ID and password again",
+
+        // 日志与诊断
+        "日志与诊断..." => "Logs & diagnostics...",
+        "日志与诊断" => "Logs & diagnostics",
+        "最近一次登录" => "Last login attempt",
+        "还没有登录记录" => "No login attempts yet",
+        "网络检查" => "Network check",
+        "访问认证页面" => "Portal probe",
+        "获取 pageInfo" => "Fetch pageInfo",
+        "加密密码" => "Encrypt password",
+        "提交登录" => "Login POST",
+        "获取用户信息" => "Fetch user info",
+        "合计" => "Total",
+        "进行中" => "In progress",
+        "{} ms" => "{} ms",
+        "最近的日志" => "Recent logs",
+        "复制到剪贴板" => "Copy to clipboard",
+        "已复制诊断信息" => "Diagnostics copied",
+
+        // 错误
+        "连接超时" => "Connection timed out",
+        "错误 {}: {}" => "Error {}: {}",
+        "未连接到 SCUNET" => "Not connected to SCUNET",
+        "无法打开 WLAN 句柄" => "Failed to open WLAN handle",
+        "无法获取 WLAN 接口列表" => "Failed to get WLAN interface list",
+        "无法获取 WLAN 连接属性" => "Failed to get WLAN connection attributes",
+        "获取用户信息失败 (但可能已登录成功)" => "Failed to get user info (but the login may have succeeded)",
+        "注销失败，仍处于登录状态" => "Logout failed, still logged in",
+        "配置文件有误" => "Invalid config file",
+        "无法读取密码文件" => "Failed to read password file",
+        "无法从标准输入读取密码" => "Failed to read password from stdin",
+        "密码命令执行失败" => "Password command failed",
+        "外部来源提供的密码为空" => "The password from the external source is empty",
+        "主密码错误或加密的账号已损坏" => "Wrong passphrase or corrupted encrypted account",
+        "账号加密的密钥不可用" => "The account encryption key is not available",
+        "加密账号失败" => "Failed to encrypt the account",
+        "无法解锁账号" => "Failed to unlock the account",
+        "配置文件中的账号已加密，请通过 {} 环境变量提供主密码" => "The account in the config file is encrypted, please provide the passphrase via the {} environment variable",
+        "命令行参数或环境变量有误" => "Invalid command-line argument or environment variable",
+        "保存配置失败" => "Failed to save config",
+        "配置文件读取失败，修复后才能保存设置" => "The config file could not be read, fix it before saving settings",
+        "配置文件版本 {} 高于程序支持的版本 {}，请更新程序" => "Config file version {} is newer than the supported version {}, please update the app",
+        "第 {} 行: {}" => "line {}: {}",
+        "学号只能包含数字" => "Student ID may only contain digits",
+        "开机启动延迟应在 0 到 5 秒之间" => "Boot delay should be between 0 and 5 seconds",
+        "低时长提醒不能为负数" => "Low time alert can't be negative",
+        "时长用完后只能切换到运营商服务" => "Can only switch to a carrier service when time runs out",
+        "检查间隔不能小于 10 秒" => "Check interval can't be less than 10 seconds",
+        "界面缩放应在 0.5 到 3 之间" => "UI scale should be between 0.5 and 3",
+        "日志级别应为 error、warn、info、debug 或 trace" => "Log level should be error, warn, info, debug or trace",
+        "监听地址格式应为 IP:端口" => "Listen address should be in IP:port format",
+        "Webhook 地址应以 http:// 或 https:// 开头" => "Webhook URL should start with http:// or https://",
+        "未知错误" => "Unknown error",
+        "未知位置" => "unknown location",
+        "HTTP 接口未启动: 请先在配置文件中设置 token" => "HTTP API not started: please set a token in the config file first",
+        "HTTP 接口启动失败: {}" => "Failed to start HTTP API: {}",
+        "指标接口启动失败: {}" => "Failed to start metrics endpoint: {}",
+        "找不到中文字体，请在配置文件中设置 font_path" => "No CJK font found, set font_path in the config file",
+
+        // 通知
+        "{greeting}" => "Welcome, {name}",
+        "你已登录到 SCUNET ({service})\n剩余时间: {left_hour} 小时" => "Logged in to SCUNET ({service})\nTime left: {left_hour} h",
+        "登录失败" => "Login failed",
+        "{error}\n请手动调整配置或检查网络状态" => "{error}\nPlease check your settings or network connection",
+        "你已登录到 SCUNET" => "Already logged in to SCUNET",
+        "你可以再次\"登录\"来更新配置" => "Log in again to update your settings",
+        "已注销 SCUNET" => "Logged out of SCUNET",
+        "SCUNET 已掉线" => "SCUNET connection lost",
+        "{service} 登录已失效，正在重新登录" => "{service} session expired, logging in again",
+        "校园网时长不足" => "Campus network time running low",
+        "剩余时间仅剩 {left_hour} 小时\n预计将于 {exhaustion} 用完\n{suggestion}" => "Only {left_hour} h left\nExpected to run out on {exhaustion}\n{suggestion}",
+        "可以考虑切换到运营商服务" => "Consider switching to a carrier service",
+        "已切换到{service}" => "Switched to {service}",
+        "AutoSCUNET 正在后台运行" => "AutoSCUNET is running in the background",
+        "后台模式下没有窗口，退出后台模式后才能打开界面" => "There is no window in daemon mode, quit the daemon to open the app",
+        "😭😭😭 程序出错了" => "😭😭😭 Something went wrong",
+        "{error}\n可以考虑提一个 Issue" => "{error}\nConsider opening an issue on GitHub",
+        "已暂停自动登录，再次登录后恢复" => "Auto login paused until you log in again",
+        "已到计划注销时间" => "Scheduled logout time reached",
+        "已通过命令行注销" => "Logged out from the command line",
+        "校园网时长已用完" => "Campus network time used up",
+        "校园网时长已重置" => "Campus network time has been reset",
+        "%m 月 %d 日 %H:%M" => "%b %d %H:%M",
+        "重试" => "Retry",
+        "切换到校园网" => "Switch to campus network",
+        "注销" => "Log out",
+        "打开 GitHub Issue 页" => "Open issue page",
+
+        _ => return None,
+    };
+    Some(translated)
+}
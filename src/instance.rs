@@ -0,0 +1,273 @@
+//! 单实例锁：后启动的实例把要做的事转发给已在运行的实例后直接退出
+//!
+//! 能锁住数据目录下 `instance.lock` 的就是当前用户唯一的实例。它在本机的随机端口上监听，
+//! 并把端口和随机生成的 token 写到只有当前用户能读的 `instance.addr` 中，
+//! 不带 token 的命令一律忽略，其它用户的程序无法控制本实例
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        Arc, OnceLock,
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use scunet_login_util::Service;
+
+use crate::{cli::CLI, config::data_dir};
+
+const LOCK_FILE_NAME: &str = "instance.lock";
+
+const ADDR_FILE_NAME: &str = "instance.addr";
+
+/// 已有实例刚启动、还没写好 `instance.addr` 时，最多等这么多次
+const CONNECT_RETRIES: u32 = 10;
+
+const SERVICES: [Service; 4] = [
+    Service::Internet,
+    Service::ChinaMobile,
+    Service::ChinaTelecom,
+    Service::ChinaUnicom,
+];
+
+type Waker = Box<dyn Fn() + Send + Sync>;
+
+/// 可以转发给正在运行的实例的命令
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// 显示主窗口
+    Show,
+    Login,
+    Logout,
+    /// 更换服务并重新登录
+    SwitchService(Service),
+    /// 配置文件被修改，重新读取
+    ReloadConfig,
+}
+
+impl Command {
+    /// 根据命令行参数得出本次启动想做的事
+    pub fn from_args() -> Self {
+        if CLI.logout {
+            Command::Logout
+        } else if CLI.login || CLI.boot {
+            Command::Login
+        } else {
+            Command::Show
+        }
+    }
+
+    fn to_line(self) -> String {
+        match self {
+            Command::Show => "show".into(),
+            Command::Login => "login".into(),
+            Command::Logout => "logout".into(),
+            Command::SwitchService(service) => format!("service {}", service_arg(service)),
+            Command::ReloadConfig => "reload".into(),
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().split_once(' ') {
+            Some(("service", arg)) => parse_service(arg).map(Command::SwitchService),
+            Some(_) => None,
+            None => match s.trim() {
+                "show" => Some(Command::Show),
+                "login" => Some(Command::Login),
+                "logout" => Some(Command::Logout),
+                "reload" => Some(Command::ReloadConfig),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// 当前实例收到的命令
+pub struct Instance {
+    pub commands: Receiver<Command>,
+    sender: CommandSender,
+}
+
+impl Instance {
+    /// 设置收到命令时的回调，用于唤醒界面
+    pub fn set_waker(&self, waker: impl Fn() + Send + Sync + 'static) {
+        self.sender.waker.set(Box::new(waker)).ok();
+    }
+
+    /// 用于在本进程内给自己发命令
+    pub fn sender(&self) -> CommandSender {
+        self.sender.clone()
+    }
+}
+
+/// 往当前实例的命令队列里放命令
+#[derive(Clone)]
+pub struct CommandSender {
+    tx: Sender<Command>,
+    waker: Arc<OnceLock<Waker>>,
+}
+
+impl CommandSender {
+    pub fn send(&self, command: Command) {
+        if self.tx.send(command).is_ok()
+            && let Some(wake) = self.waker.get()
+        {
+            wake();
+        }
+    }
+}
+
+/// 尝试成为唯一的实例
+///
+/// 已有实例在运行时把 `command` 转发过去并返回 `None`，调用者应直接退出；
+/// 无法加锁或转发失败时照常运行，只是收不到其它进程的命令。应在读取配置后调用，问题记录在日志中
+pub fn acquire(command: Command) -> Option<Instance> {
+    let (tx, rx) = channel();
+    let sender = CommandSender {
+        tx,
+        waker: Arc::default(),
+    };
+
+    match lock() {
+        Ok(Some(lock)) => {
+            if let Err(e) = listen(lock, sender.clone()) {
+                tracing::warn!(error = format!("{:#}", e), "无法接收其它进程的命令");
+            }
+        }
+        Ok(None) => {
+            if send(command).is_ok() {
+                return None;
+            }
+        }
+        Err(e) => tracing::warn!(error = format!("{:#}", e), "无法获取单实例锁"),
+    }
+
+    Some(Instance {
+        commands: rx,
+        sender,
+    })
+}
+
+/// 锁住 `instance.lock`，已被其它实例锁住时返回 `None`
+fn lock() -> Result<Option<File>> {
+    let file = File::create(data_dir()?.join(LOCK_FILE_NAME))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// 在随机端口上监听，`lock` 随监听线程一直持有到进程退出
+fn listen(lock: File, sender: CommandSender) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+
+    let mut token = [0u8; 16];
+    OsRng.fill_bytes(&mut token);
+    let token: String = token.iter().map(|b| format!("{:02x}", b)).collect();
+    write_addr_file(&format!("{} {}\n", port, token)).context("无法写入 instance.addr")?;
+
+    thread::spawn(move || {
+        let _lock = lock;
+        for stream in listener.incoming().flatten() {
+            if let Some(command) = read_command(&stream, &token) {
+                sender.send(command);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 只有当前用户能读写，其它用户拿不到 token
+fn write_addr_file(contents: &str) -> Result<()> {
+    let path = addr_file()?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+fn addr_file() -> Result<PathBuf> {
+    Ok(data_dir()?.join(ADDR_FILE_NAME))
+}
+
+fn read_command(mut stream: &TcpStream, token: &str) -> Option<Command> {
+    stream.set_read_timeout(Some(Duration::from_secs(1))).ok()?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    let (received, command) = line.trim().split_once(' ')?;
+    if !token_eq(received, token) {
+        return None;
+    }
+    let command = Command::parse(command)?;
+
+    stream.write_all(b"ok\n").ok()?;
+    Some(command)
+}
+
+/// 把命令发给正在运行的实例，可能就是自己
+pub fn send(command: Command) -> Result<()> {
+    let mut retries = 0;
+    let (port, token) = loop {
+        let addr = std::fs::read_to_string(addr_file()?).unwrap_or_default();
+        if let Some((port, token)) = addr.trim().split_once(' ')
+            && let Ok(port) = port.parse::<u16>()
+        {
+            break (port, token.to_string());
+        }
+        retries += 1;
+        if retries >= CONNECT_RETRIES {
+            bail!("instance.addr 无效");
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    writeln!(stream, "{} {}", token, command.to_line())?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    if reply.trim() == "ok" {
+        Ok(())
+    } else {
+        bail!("实例拒绝了命令")
+    }
+}
+
+/// 比较 token，耗时与不同之处的位置无关
+pub fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// 按 `service_arg` 的写法解析服务
+pub fn parse_service(arg: &str) -> Option<Service> {
+    SERVICES
+        .into_iter()
+        .find(|service| service_arg(*service) == arg)
+}
+
+/// 服务在命令行、指标、钩子等对外接口中的写法
+pub fn service_arg(service: Service) -> &'static str {
+    match service {
+        Service::Internet => "internet",
+        Service::ChinaMobile => "china_mobile",
+        Service::ChinaTelecom => "china_telecom",
+        Service::ChinaUnicom => "china_unicom",
+    }
+}
@@ -0,0 +1,282 @@
+//! 运行日志
+//!
+//! 日志按天写到数据目录下的 `logs` 中，只保留最近几天的，最近的几百行还会留在内存中供诊断窗口显示。
+//! 格式化时会把密码、token、userIndex 等字段和加密后的内容替换掉，日志可以直接附在 Issue 里
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    io::{self, Write},
+    sync::{Mutex, OnceLock, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{Metadata, field::Field};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    Layer,
+    field::MakeExt,
+    filter::{LevelFilter, filter_fn},
+    fmt::{
+        self, FormatFields,
+        format::{Writer, debug_fn},
+        time::ChronoLocal,
+    },
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
+
+use crate::{config::data_dir, diagnostics::DiagnosticsLayer};
+
+const LOG_DIR_NAME: &str = "logs";
+
+/// 写进日志前要替换掉值的字段
+const SECRET_KEYS: [&str; 5] = ["password", "passphrase", "token", "ciphertext", "userIndex"];
+
+/// 内存中最多保留的行数
+const MAX_RECENT_LINES: usize = 500;
+
+static INITIALIZED: OnceLock<()> = OnceLock::new();
+
+/// 写进日志文件和内存的级别，可以随配置文件更新
+static LEVEL: RwLock<LevelFilter> = RwLock::new(LevelFilter::INFO);
+
+static RECENT_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// 日志级别: error, warn, info, debug, trace
+    pub level: String,
+    /// 最多保留几天的日志
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".into(),
+            max_files: 7,
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn level_filter(&self) -> Option<LevelFilter> {
+        self.level.parse().ok()
+    }
+}
+
+/// 第一次调用时开始写日志，之后只更新日志级别
+pub fn init(config: &LogConfig) {
+    *LEVEL.write().unwrap() = config.level_filter().unwrap_or(LevelFilter::INFO);
+    if INITIALIZED.set(()).is_err() {
+        return;
+    }
+
+    let appender = data_dir().ok().and_then(|dir| {
+        let dir = dir.join(LOG_DIR_NAME);
+        // 目录不存在时清理旧日志会报错
+        std::fs::create_dir_all(&dir).ok()?;
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("auto-scunet")
+            .filename_suffix("log")
+            .max_log_files(config.max_files.max(1))
+            .build(dir)
+            .ok()
+    });
+
+    let file = appender.map(|appender| {
+        fmt::layer()
+            .with_ansi(false)
+            .with_timer(ChronoLocal::new("%Y-%m-%d %H:%M:%S%.3f".into()))
+            .fmt_fields(redacted_fields())
+            .with_writer(appender)
+            .with_filter(filter_fn(|meta| enabled(meta, *LEVEL.read().unwrap())))
+    });
+    let memory = fmt::layer()
+        .with_ansi(false)
+        .with_target(false)
+        .with_timer(ChronoLocal::new("%H:%M:%S%.3f".into()))
+        .fmt_fields(redacted_fields())
+        .with_writer(|| RecentLines)
+        .with_filter(filter_fn(|meta| enabled(meta, *LEVEL.read().unwrap())));
+    // 日志级别调低后诊断窗口也要能看到每一步
+    let diagnostics =
+        DiagnosticsLayer.with_filter(filter_fn(|meta| enabled(meta, LevelFilter::INFO)));
+
+    tracing_subscriber::registry()
+        .with(file)
+        .with(memory)
+        .with(diagnostics)
+        .try_init()
+        .ok();
+}
+
+/// 只记录本程序和登录库的日志，不要依赖库的
+fn enabled(meta: &Metadata, level: LevelFilter) -> bool {
+    let target = meta.target();
+    (target.starts_with("auto_scunet") || target.starts_with("scunet_login_util"))
+        && *meta.level() <= level
+}
+
+/// 内存中最近的日志，旧的在前
+pub fn recent_lines() -> Vec<String> {
+    RECENT_LINES.lock().unwrap().iter().cloned().collect()
+}
+
+/// 按字段格式化，敏感字段的值整个替换掉，其它字段的值再经过 [`redact`]
+fn redacted_fields() -> impl for<'w> FormatFields<'w> + 'static {
+    debug_fn(
+        |writer: &mut Writer<'_>, field: &Field, value: &dyn Debug| {
+            let name = field.name();
+            if is_secret(name) {
+                write!(writer, "{}=<redacted>", name)
+            } else if name == "message" {
+                write!(writer, "{}", redact(&format!("{:?}", value)))
+            } else {
+                write!(writer, "{}={}", name, redact(&format!("{:?}", value)))
+            }
+        },
+    )
+    .delimited(" ")
+}
+
+fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.iter().any(|secret| key.ends_with(secret))
+}
+
+/// 替换掉一段文字中 `password=...` 形式的内容和加密后的内容
+pub fn redact(line: &str) -> String {
+    let (text, newline) = match line.strip_suffix('\n') {
+        Some(text) => (text, "\n"),
+        None => (line, ""),
+    };
+
+    let words: Vec<String> = text
+        .split(' ')
+        .map(|word| match word.split_once('=') {
+            Some((key, _)) if is_secret(key) => {
+                format!("{}=<redacted>", key)
+            }
+            _ if is_encrypted(word) => "<redacted>".into(),
+            _ => word.into(),
+        })
+        .collect();
+    words.join(" ") + newline
+}
+
+/// 看起来像加密后的密码 (很长的十六进制串)
+fn is_encrypted(word: &str) -> bool {
+    let word = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    word.len() >= 64 && word.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 把日志写到内存中的 [`RECENT_LINES`]
+struct RecentLines;
+
+impl Write for RecentLines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = RECENT_LINES.lock().unwrap();
+        if lines.len() >= MAX_RECENT_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(String::from_utf8_lossy(buf).trim_end().to_string());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tracing_subscriber::fmt::fmt;
+
+    use super::*;
+
+    #[test]
+    fn redact_secret_pairs() {
+        assert_eq!(
+            redact("login password=abc service=Internet\n"),
+            "login password=<redacted> service=Internet\n"
+        );
+        assert_eq!(redact("userIndex=1234 ok"), "userIndex=<redacted> ok");
+        assert_eq!(redact("http_api.token=x"), "http_api.token=<redacted>");
+    }
+
+    #[test]
+    fn redact_encrypted_hex() {
+        let hex = "ab12".repeat(32);
+        assert_eq!(
+            redact(&format!("加密结果 \"{}\"", hex)),
+            "加密结果 <redacted>"
+        );
+        // 较短的十六进制串不是加密后的密码
+        assert_eq!(redact("id deadbeef"), "id deadbeef");
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn format(log: impl FnOnce()) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = fmt()
+            .with_ansi(false)
+            .without_time()
+            .with_target(false)
+            .with_level(false)
+            .fmt_fields(redacted_fields())
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, log);
+        let output = buffer.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn secret_fields_are_masked_whole() {
+        let output = format(|| {
+            tracing::info!(password = "a b c", service = "Internet", "登录");
+        });
+        assert_eq!(
+            output.trim_end(),
+            "登录 password=<redacted> service=\"Internet\""
+        );
+    }
+
+    #[test]
+    fn secret_span_fields_are_masked() {
+        let output = format(|| {
+            let span = tracing::info_span!("login", userIndex = "x y", service = "Internet");
+            let _guard = span.enter();
+            tracing::info!("开始登录");
+        });
+        assert!(output.contains("userIndex=<redacted>"), "{}", output);
+        assert!(!output.contains("x y"), "{}", output);
+    }
+
+    #[test]
+    fn secrets_in_messages_are_redacted() {
+        let output = format(|| {
+            tracing::warn!(error = "bad password=hunter2", "登录失败");
+        });
+        assert!(!output.contains("hunter2"), "{}", output);
+    }
+}
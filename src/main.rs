@@ -1,37 +1,132 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod app;
+mod cli;
 mod config;
+mod credentials;
+mod daemon;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod diagnostics;
+mod font;
+mod history;
+mod hooks;
+mod http_api;
+mod i18n;
+mod instance;
+mod logging;
+mod metrics;
+mod notification;
+mod password;
+mod profile;
+mod schedule;
+mod schema;
+mod switch;
 mod toast;
+mod webhook;
 
-use std::{process::exit, thread::sleep, time::Duration};
+use std::{
+    process::exit,
+    thread::{JoinHandle, sleep},
+    time::{Duration, Instant},
+};
 
+use anyhow::Context;
 use app::{AutoScunetApp, AutoScunetAppParam};
 use config::*;
+use hooks::HookEvent;
+use i18n::{error_text, t, tf};
+use instance::Command;
+use notification::{LogoutReason, NotificationEvent, notify};
 use scunet_login_util::*;
-use toast::*;
 
 fn main() -> Result<(), eframe::Error> {
     set_panic_hook();
 
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([320.0, 180.0])
-            .with_resizable(false)
-            .with_maximize_button(false)
-            .with_minimize_button(false)
-            .with_icon(ICON_DATA.clone()),
-        centered: true,
-        ..Default::default()
+    // 导入导出只读写配置文件后就退出，已有实例在运行时也不用交给它
+    if cli::CLI.export.is_some() || cli::CLI.import.is_some() {
+        run_profile_command();
+    }
+
+    let command = Command::from_args();
+    let daemon = cli::CLI.daemon;
+
+    // 要先读取配置才知道是不是便携模式，单实例锁和数据放在同一个目录下
+    // 配置有误时不能悄悄用默认配置覆盖掉，至少要让用户看到错误
+    let (config, config_error) = match load_config() {
+        Ok(config) => (config, None),
+        Err(e) => {
+            let message = report_config_error(e);
+            if daemon || command == Command::Logout {
+                exit(1);
+            }
+            (AppConfig::default(), Some(message))
+        }
     };
 
+    // 已有实例在运行时，交给它处理就行
+    let Some(instance) = instance::acquire(command) else {
+        exit(0);
+    };
+
+    // 没有界面可以输入主密码
+    if credentials::is_locked(&config) && (daemon || command == Command::Logout) {
+        let message = tf(
+            "配置文件中的账号已加密，请通过 {} 环境变量提供主密码",
+            &[&credentials::PASSPHRASE_ENV],
+        );
+        eprintln!("{}", message);
+        notify(NotificationEvent::Error { message });
+        exit(1);
+    }
+
+    tracing::info!(?command, daemon, "启动");
+
+    if daemon {
+        daemon::run(config, instance);
+    }
+
+    if command == Command::Logout {
+        match logout(&config, config.service) {
+            Ok(_) => notify(NotificationEvent::LoggedOut {
+                reason: LogoutReason::CommandLine,
+            }),
+            Err(e) => notify(NotificationEvent::LoginFailure {
+                error: error_text(&e),
+            }),
+        }
+        exit_when_done(0);
+    }
+
     let mut param = AutoScunetAppParam {
-        config: load_config().unwrap_or_default(),
+        config,
         logged_in: false,
         not_connected_to_scunet: false,
+        config_error,
+        instance,
     };
 
-    pre_login(&mut param);
+    // 配置有误时用的是默认配置，没有账号可以登录
+    if param.config_error.is_none() {
+        pre_login(&mut param);
+    }
+
+    let config = &param.config;
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(config.window_size())
+        .with_resizable(config.resizable)
+        .with_maximize_button(config.resizable)
+        .with_minimize_button(false)
+        .with_icon(ICON_DATA.clone());
+    if let Some(pos) = config.window_pos {
+        viewport = viewport.with_position(pos);
+    }
+
+    let options = eframe::NativeOptions {
+        viewport,
+        centered: config.window_pos.is_none(),
+        ..Default::default()
+    };
 
     eframe::run_native(
         &format!("AutoSCUNET v{}", VERSION),
@@ -42,13 +137,10 @@ fn main() -> Result<(), eframe::Error> {
 
 fn pre_login(param: &mut AutoScunetAppParam) {
     let config = &mut param.config;
-
-    let mut login_util = ScunetLoginUtil::builder()
-        .student_id(&config.student_id)
-        .password(&config.password)
-        .service(config.service)
-        .on_boot(*ON_BOOT.get().unwrap())
-        .build();
+    // 等用户在界面上解锁后再登录
+    if credentials::is_locked(config) {
+        return;
+    }
 
     let on_boot = *ON_BOOT.get().unwrap();
     let delay = config.on_boot_delay;
@@ -56,24 +148,32 @@ fn pre_login(param: &mut AutoScunetAppParam) {
         sleep(Duration::from_secs(delay as u64));
     }
 
-    match login_util.login() {
+    let result = password::resolve(config).and_then(|password| {
+        ScunetLoginUtil::builder()
+            .student_id(&config.student_id)
+            .password(&password)
+            .service(switch::prepare_service(config))
+            .on_boot(on_boot)
+            .build()
+            .login()
+    });
+    password::forget_if_rejected(&result);
+
+    match result {
         Ok(LoginStatus::Success(user_info)) => {
-            config.password = user_info.encrypted_password;
-            Toast::success(
-                user_info.userName,
-                user_info.welcomeTip,
-                user_info.left_hour,
-                user_info.service,
-                config,
-            );
-            save_config(config).unwrap();
-            exit(0);
+            let user_info = switch::apply(config, user_info);
+            if let Err(e) = on_login_success(config, user_info) {
+                notify(NotificationEvent::Error {
+                    message: format!("{:#}", e),
+                });
+            }
+            exit_when_done(0);
         }
         Ok(LoginStatus::HaveLoggedIn) => {
             param.logged_in = true;
             if on_boot {
-                Toast::logged_in();
-                exit(0);
+                notify(NotificationEvent::AlreadyLoggedIn);
+                exit_when_done(0);
             }
         }
         Err(e) => {
@@ -85,12 +185,128 @@ fn pre_login(param: &mut AutoScunetAppParam) {
                     param.not_connected_to_scunet = true;
                 }
             } else {
-                Toast::fail(e);
+                hooks::run(HookEvent::Failure {
+                    error: error_text(&e),
+                });
+                notify(NotificationEvent::LoginFailure {
+                    error: error_text(&e),
+                });
             }
         }
     }
 }
 
+/// 登录成功后的收尾工作：保存加密后的密码，发送通知并记录剩余时长
+///
+/// 密码来自外部来源时不保存
+pub fn on_login_success(config: &mut AppConfig, user_info: OnlineUserInfo) -> anyhow::Result<()> {
+    if !password::has_external_source(config) {
+        config.password = user_info.encrypted_password;
+    }
+    hooks::run(HookEvent::Success {
+        name: user_info.userName.clone(),
+        service: user_info.service,
+        left_hour: user_info.left_hour,
+    });
+    notify(NotificationEvent::LoginSuccess {
+        name: if config.greeting_name.is_empty() {
+            user_info.userName
+        } else {
+            config.greeting_name.clone()
+        },
+        tip: user_info.welcomeTip,
+        service: user_info.service,
+        left_hour: user_info.left_hour,
+    });
+    if let Some(left_hour) = user_info.left_hour {
+        history::record_left_hour(left_hour, config).ok();
+    }
+    save_config(config).with_context(|| t("保存配置失败").to_string())
+}
+
+/// 等钩子命令执行完、Webhook 发送完再退出，直接退出会把后台线程一起结束掉
+pub fn exit_when_done(code: i32) -> ! {
+    hooks::wait();
+    webhook::wait();
+    exit(code)
+}
+
+/// 等待后台线程结束，到 `deadline` 还没结束的不再等
+pub fn join_until(handles: Vec<JoinHandle<()>>, deadline: Instant) {
+    for handle in handles {
+        while !handle.is_finished() && Instant::now() < deadline {
+            sleep(Duration::from_millis(50));
+        }
+        if handle.is_finished() {
+            handle.join().ok();
+        }
+    }
+}
+
+/// 报告配置文件读取失败，返回错误信息
+fn report_config_error(e: anyhow::Error) -> String {
+    let message = format!("{:#}", e);
+    logging::init(&Default::default());
+    eprintln!("{}", message);
+    notify(NotificationEvent::Error {
+        message: message.clone(),
+    });
+    message
+}
+
+/// 执行 `--export` 或 `--import` 后退出
+fn run_profile_command() -> ! {
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            report_config_error(e);
+            exit(1)
+        }
+    };
+
+    if let Some(path) = &cli::CLI.export {
+        finish(
+            profile::export(&config, path, cli::CLI.redact),
+            "配置已导出到 {}",
+            path,
+        );
+    }
+    let path = cli::CLI.import.as_ref().unwrap();
+    // 正在运行的实例会发现配置文件被修改并重新读取
+    let result = profile::import(&config, path).and_then(|config| save_config(&config));
+    finish(result, "已从 {} 导入配置", path)
+}
+
+/// 在命令行中报告导入或导出的结果并退出
+fn finish(result: anyhow::Result<()>, success: &str, path: &std::path::Path) -> ! {
+    match result {
+        Ok(()) => {
+            println!("{}", tf(success, &[&path.display()]));
+            exit(0)
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            exit(1)
+        }
+    }
+}
+
+/// 用配置中的账号注销当前登录
+pub fn logout(config: &AppConfig, service: Service) -> anyhow::Result<LogoutStatus> {
+    let status = ScunetLoginUtil::builder()
+        .student_id(&config.student_id)
+        .password(&config.password)
+        .service(service)
+        .build()
+        .logout()?;
+
+    if status == LogoutStatus::Success {
+        hooks::run(HookEvent::Logout { service });
+    }
+
+    Ok(status)
+}
+
 fn set_panic_hook() {
     std::panic::set_hook(Box::new(|info| {
         let msg = if let Some(s) = info.payload().downcast_ref::<&str>() {
@@ -98,15 +314,17 @@ fn set_panic_hook() {
         } else if let Some(s) = info.payload().downcast_ref::<String>() {
             s.clone()
         } else {
-            "未知错误".to_string()
+            t("未知错误").to_string()
         };
 
         let location = if let Some(loc) = info.location() {
             format!("'{}' at line {}", loc.file(), loc.line())
         } else {
-            "未知位置".to_string()
+            t("未知位置").to_string()
         };
 
-        Toast::error(format!("{}:\n{}", location, msg));
+        notify(NotificationEvent::Error {
+            message: format!("{}:\n{}", location, msg),
+        });
     }));
 }
@@ -0,0 +1,202 @@
+//! 后台模式下的 Prometheus 指标
+
+use std::{collections::BTreeMap, fmt::Write, thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Response, Server};
+
+use crate::{
+    daemon::{DaemonStatus, SharedDaemon},
+    i18n::tf,
+    instance::service_arg,
+    notification::{NotificationEvent, notify},
+};
+
+use scunet_login_util::*;
+
+/// 登录耗时直方图的分桶 (秒)
+const LATENCY_BUCKETS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0];
+
+/// 指标接口的配置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enable: bool,
+    pub listen: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            listen: "127.0.0.1:9936".into(),
+        }
+    }
+}
+
+/// 后台模式累计的登录统计
+#[derive(Debug, Default)]
+pub struct Metrics {
+    attempts: BTreeMap<&'static str, u64>,
+    successes: BTreeMap<&'static str, u64>,
+    /// 以 (服务, 错误类型) 为键
+    failures: BTreeMap<(&'static str, &'static str), u64>,
+    latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_sum: f64,
+    latency_count: u64,
+}
+
+impl Metrics {
+    /// 记录一次登录尝试的结果和耗时
+    ///
+    /// 每次检查都会调用登录，已经在线时并没有真正提交登录，不计入统计
+    pub fn record(
+        &mut self,
+        service: Service,
+        result: &anyhow::Result<LoginStatus>,
+        elapsed: Duration,
+    ) {
+        if let Ok(LoginStatus::HaveLoggedIn) = result {
+            return;
+        }
+        let service = service_arg(service);
+        *self.attempts.entry(service).or_default() += 1;
+
+        match result {
+            Ok(_) => *self.successes.entry(service).or_default() += 1,
+            Err(e) => *self.failures.entry((service, error_kind(e))).or_default() += 1,
+        }
+
+        let secs = elapsed.as_secs_f64();
+        for (bucket, le) in self.latency_buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if secs <= le {
+                *bucket += 1;
+            }
+        }
+        self.latency_sum += secs;
+        self.latency_count += 1;
+    }
+
+    /// 按 Prometheus 文本格式输出
+    pub fn render(&self, status: &DaemonStatus) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP auto_scunet_online Whether the device is logged in to SCUNET.\n");
+        out.push_str("# TYPE auto_scunet_online gauge\n");
+        writeln!(
+            out,
+            "auto_scunet_online {}",
+            (status.online == Some(true)) as u8
+        )
+        .ok();
+
+        if let Some(left_hour) = status.left_hour {
+            out.push_str("# HELP auto_scunet_left_hours Remaining free campus network hours.\n");
+            out.push_str("# TYPE auto_scunet_left_hours gauge\n");
+            writeln!(out, "auto_scunet_left_hours {}", left_hour).ok();
+        }
+
+        for (name, help, counter) in [
+            (
+                "auto_scunet_login_attempts_total",
+                "Login attempts.",
+                &self.attempts,
+            ),
+            (
+                "auto_scunet_login_successes_total",
+                "Successful logins.",
+                &self.successes,
+            ),
+        ] {
+            writeln!(out, "# HELP {} {}\n# TYPE {} counter", name, help, name).ok();
+            for (service, count) in counter {
+                writeln!(out, "{}{{service=\"{}\"}} {}", name, service, count).ok();
+            }
+        }
+
+        out.push_str("# HELP auto_scunet_login_failures_total Failed logins by error kind.\n");
+        out.push_str("# TYPE auto_scunet_login_failures_total counter\n");
+        for ((service, kind), count) in &self.failures {
+            writeln!(
+                out,
+                "auto_scunet_login_failures_total{{service=\"{}\",kind=\"{}\"}} {}",
+                service, kind, count
+            )
+            .ok();
+        }
+
+        out.push_str("# HELP auto_scunet_login_duration_seconds Time spent on a login attempt.\n");
+        out.push_str("# TYPE auto_scunet_login_duration_seconds histogram\n");
+        for (count, le) in self.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+            writeln!(
+                out,
+                "auto_scunet_login_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, count
+            )
+            .ok();
+        }
+        writeln!(
+            out,
+            "auto_scunet_login_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.latency_count
+        )
+        .ok();
+        writeln!(
+            out,
+            "auto_scunet_login_duration_seconds_sum {}",
+            self.latency_sum
+        )
+        .ok();
+        writeln!(
+            out,
+            "auto_scunet_login_duration_seconds_count {}",
+            self.latency_count
+        )
+        .ok();
+
+        out
+    }
+}
+
+/// 按配置在后台线程中启动指标接口
+pub fn start(daemon: &SharedDaemon) {
+    let config = daemon.lock().unwrap().config().metrics.clone();
+    if !config.enable {
+        return;
+    }
+
+    let server = match Server::http(&config.listen) {
+        Ok(server) => server,
+        Err(e) => {
+            notify(NotificationEvent::Error {
+                message: tf("指标接口启动失败: {}", &[&e]),
+            });
+            return;
+        }
+    };
+
+    let daemon = daemon.clone();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                Response::from_string(daemon.lock().unwrap().render_metrics()).with_header(
+                    Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap(),
+                )
+            } else {
+                Response::from_string("not found").with_status_code(404)
+            };
+            request.respond(response).ok();
+        }
+    });
+}
+
+fn error_kind(e: &anyhow::Error) -> &'static str {
+    match e.downcast_ref::<LoginError>() {
+        Some(LoginError::Fail(_)) => "rejected",
+        Some(LoginError::TimeOut) => "timeout",
+        Some(LoginError::WiFiStatusError(..)) => "wifi",
+        Some(LoginError::NotConnectedToScunet) => "not_connected",
+        // 其它错误都来自 HTTP 请求或响应解析
+        None => "request",
+    }
+}
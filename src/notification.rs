@@ -0,0 +1,526 @@
+//! 通知事件与通知渠道
+//!
+//! 所有需要告知用户的事情都表示为 [`NotificationEvent`]，由 [`notify`] 分发给配置中
+//! 启用的各个 [`Notifier`]，这样无头设备也能通过标准输出、文件或 Webhook 得知结果
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{LazyLock, RwLock},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppConfig,
+    cli::CLI,
+    config::data_dir,
+    i18n::{self, service_name, t},
+    toast::DesktopNotifier,
+};
+
+use scunet_login_util::Service;
+
+const NOTIFICATION_LOG_FILE_NAME: &str = "notifications.log";
+
+/// 读取配置前 (或配置读取失败时) 只使用桌面通知
+static NOTIFIERS: LazyLock<RwLock<Vec<Box<dyn Notifier>>>> = LazyLock::new(|| {
+    // 解析命令行参数出错时也会用到，这时不能读取 CLI
+    RwLock::new(vec![Box::new(DesktopNotifier {
+        enabled: true,
+        actions: false,
+    })])
+});
+
+/// 当前的自定义模板
+static SETTINGS: LazyLock<RwLock<NotificationConfig>> = LazyLock::new(Default::default);
+
+/// 需要通知用户的事件
+#[derive(Clone, Debug)]
+pub enum NotificationEvent {
+    LoginSuccess {
+        /// 问候称呼，未设置时为真实姓名
+        name: String,
+        tip: String,
+        service: Service,
+        left_hour: Option<f64>,
+    },
+    LoginFailure {
+        error: String,
+    },
+    /// 启动时发现已经登录
+    AlreadyLoggedIn,
+    LoggedOut {
+        reason: LogoutReason,
+    },
+    /// 后台模式检查时发现已经掉线
+    SessionLost {
+        service: Service,
+    },
+    LowHour {
+        left_hour: f64,
+        exhaustion: Option<DateTime<Local>>,
+    },
+    ServiceSwitched {
+        from: Service,
+        to: Service,
+        reason: SwitchReason,
+    },
+    /// 后台模式正在运行，打不开界面
+    DaemonRunning,
+    /// 程序本身出错，不受通知开关影响
+    Error {
+        message: String,
+    },
+}
+
+/// 注销的原因
+#[derive(Clone, Copy, Debug)]
+pub enum LogoutReason {
+    /// 后台模式下手动注销，暂停自动登录
+    Paused,
+    /// 到了计划任务的注销时间
+    Scheduled,
+    /// 通过 `--logout` 注销
+    CommandLine,
+}
+
+/// 自动切换服务的原因
+#[derive(Clone, Copy, Debug)]
+pub enum SwitchReason {
+    /// 校园网时长用完
+    Exhausted,
+    /// 校园网时长每月重置
+    Reset,
+}
+
+/// 通知上的操作按钮，点击后转发给正在运行的实例处理
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotificationAction {
+    Retry,
+    SwitchToCampus,
+    Logout,
+    OpenIssuePage,
+}
+
+const ACTIONS: [NotificationAction; 4] = [
+    NotificationAction::Retry,
+    NotificationAction::SwitchToCampus,
+    NotificationAction::Logout,
+    NotificationAction::OpenIssuePage,
+];
+
+impl NotificationAction {
+    pub fn id(self) -> &'static str {
+        match self {
+            NotificationAction::Retry => "retry",
+            NotificationAction::SwitchToCampus => "switch_to_campus",
+            NotificationAction::Logout => "logout",
+            NotificationAction::OpenIssuePage => "open_issue_page",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        ACTIONS.into_iter().find(|action| action.id() == id)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotificationAction::Retry => t("重试"),
+            NotificationAction::SwitchToCampus => t("切换到校园网"),
+            NotificationAction::Logout => t("注销"),
+            NotificationAction::OpenIssuePage => t("打开 GitHub Issue 页"),
+        }
+    }
+}
+
+impl NotificationEvent {
+    /// 事件名，用于 Webhook、日志和模板配置
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationEvent::LoginSuccess { .. } => "login_success",
+            NotificationEvent::LoginFailure { .. } => "login_failure",
+            NotificationEvent::AlreadyLoggedIn => "already_logged_in",
+            NotificationEvent::DaemonRunning => "daemon_running",
+            NotificationEvent::LoggedOut { .. } => "logged_out",
+            NotificationEvent::SessionLost { .. } => "session_lost",
+            NotificationEvent::LowHour { .. } => "low_hour",
+            NotificationEvent::ServiceSwitched { .. } => "service_switched",
+            NotificationEvent::Error { .. } => "error",
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let (title, _) = self.template();
+        render(&title, self).unwrap_or_default()
+    }
+
+    /// 通知正文，每项一行
+    ///
+    /// 含有空占位符的行会被省略，比如没有剩余时长时就不显示剩余时间那一行
+    pub fn body(&self) -> Vec<String> {
+        let (_, body) = self.template();
+        body.lines().filter_map(|line| render(line, self)).collect()
+    }
+
+    /// 通知上显示的操作按钮
+    ///
+    /// 按钮只在后台模式下显示，没有窗口，所以没有打开设置的按钮
+    pub fn actions(&self) -> Vec<NotificationAction> {
+        use NotificationAction::*;
+
+        match self {
+            NotificationEvent::LoginSuccess { service, .. } if *service != Service::Internet => {
+                vec![SwitchToCampus, Logout]
+            }
+            NotificationEvent::LoginSuccess { .. } => vec![Logout],
+            NotificationEvent::LoginFailure { .. } => vec![Retry],
+            NotificationEvent::AlreadyLoggedIn => vec![Logout],
+            NotificationEvent::LoggedOut { .. } => vec![Retry],
+            NotificationEvent::SessionLost { .. } => vec![],
+            NotificationEvent::LowHour { .. } => vec![],
+            NotificationEvent::ServiceSwitched { to, .. } if *to != Service::Internet => {
+                vec![Logout]
+            }
+            NotificationEvent::ServiceSwitched { .. } => vec![],
+            NotificationEvent::DaemonRunning => vec![],
+            NotificationEvent::Error { .. } => vec![OpenIssuePage],
+        }
+    }
+
+    /// 模板占位符 `{key}` 对应的值，事件不带该信息时为空字符串
+    pub fn field(&self, key: &str) -> Option<String> {
+        let value = match (key, self) {
+            ("event", _) => self.name().into(),
+            ("title", _) => self.title(),
+            ("message", _) => self.body().join("\n"),
+            ("time", _) => Local::now().to_rfc3339(),
+            ("name", NotificationEvent::LoginSuccess { name, .. }) => name.clone(),
+            ("tip", NotificationEvent::LoginSuccess { tip, .. }) => tip.clone(),
+            // 没有欢迎语时只显示名字，不留下多余的逗号
+            ("greeting", NotificationEvent::LoginSuccess { name, tip, .. }) => {
+                if tip.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}, {}", name, tip)
+                }
+            }
+            ("service", NotificationEvent::LoginSuccess { service, .. })
+            | ("service", NotificationEvent::SessionLost { service })
+            | ("service", NotificationEvent::ServiceSwitched { to: service, .. }) => {
+                service_name(*service).into()
+            }
+            ("from", NotificationEvent::ServiceSwitched { from, .. }) => service_name(*from).into(),
+            ("left_hour", NotificationEvent::LoginSuccess { left_hour, .. }) => {
+                left_hour.map(|h| h.to_string()).unwrap_or_default()
+            }
+            ("left_hour", NotificationEvent::LowHour { left_hour, .. }) => left_hour.to_string(),
+            ("exhaustion", NotificationEvent::LowHour { exhaustion, .. }) => exhaustion
+                .map(|t| t.format(i18n::t("%m 月 %d 日 %H:%M")).to_string())
+                .unwrap_or_default(),
+            // 估算不出耗尽时间时代替那一行
+            ("suggestion", NotificationEvent::LowHour { exhaustion, .. }) => match exhaustion {
+                Some(_) => String::new(),
+                None => t("可以考虑切换到运营商服务").into(),
+            },
+            ("reason", NotificationEvent::LoggedOut { reason }) => t(logout_reason(*reason)).into(),
+            ("reason", NotificationEvent::ServiceSwitched { reason, .. }) => {
+                t(switch_reason(*reason)).into()
+            }
+            ("error", NotificationEvent::LoginFailure { error }) => error.clone(),
+            ("error", NotificationEvent::Error { message }) => message.clone(),
+            (
+                "name" | "tip" | "greeting" | "service" | "from" | "left_hour" | "exhaustion"
+                | "suggestion" | "reason" | "error",
+                _,
+            ) => String::new(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// 该事件的标题和正文模板，优先使用配置中的自定义模板
+    fn template(&self) -> (String, String) {
+        let (title, body) = default_template(self.name());
+        let (title, body) = (t(title), t(body));
+
+        match SETTINGS.read().unwrap().templates.get(self.name()) {
+            Some(custom) => (
+                or_default(&custom.title, title),
+                or_default(&custom.body, body),
+            ),
+            None => (title.into(), body.into()),
+        }
+    }
+}
+
+fn or_default(custom: &str, default: &str) -> String {
+    if custom.is_empty() { default } else { custom }.into()
+}
+
+/// 渲染通知的一行，标题和正文里不能再引用 `{title}` 和 `{message}`；
+/// 有占位符的值为空时返回 `None`
+fn render(template: &str, event: &NotificationEvent) -> Option<String> {
+    let mut has_empty = false;
+    let line = fill_with(
+        template,
+        |key| match key {
+            "title" | "message" => None,
+            _ => event.field(key).inspect(|v| has_empty |= v.is_empty()),
+        },
+        |s| s.to_string(),
+    );
+    (!has_empty).then_some(line)
+}
+
+#[rustfmt::skip]
+fn default_template(event: &str) -> (&'static str, &'static str) {
+    match event {
+        "login_success" => ("{greeting}", "你已登录到 SCUNET ({service})\n剩余时间: {left_hour} 小时"),
+        "login_failure" => ("登录失败", "{error}\n请手动调整配置或检查网络状态"),
+        "already_logged_in" => ("你已登录到 SCUNET", "你可以再次\"登录\"来更新配置"),
+        "logged_out" => ("已注销 SCUNET", "{reason}"),
+        "session_lost" => ("SCUNET 已掉线", "{service} 登录已失效，正在重新登录"),
+        "low_hour" => ("校园网时长不足", "剩余时间仅剩 {left_hour} 小时\n预计将于 {exhaustion} 用完\n{suggestion}"),
+        "service_switched" => ("已切换到{service}", "{reason}\n{from} → {service}"),
+        "daemon_running" => ("AutoSCUNET 正在后台运行", "后台模式下没有窗口，退出后台模式后才能打开界面"),
+        _ => ("😭😭😭 程序出错了", "{error}\n可以考虑提一个 Issue"),
+    }
+}
+
+fn logout_reason(reason: LogoutReason) -> &'static str {
+    match reason {
+        LogoutReason::Paused => "已暂停自动登录，再次登录后恢复",
+        LogoutReason::Scheduled => "已到计划注销时间",
+        LogoutReason::CommandLine => "已通过命令行注销",
+    }
+}
+
+fn switch_reason(reason: SwitchReason) -> &'static str {
+    match reason {
+        SwitchReason::Exhausted => "校园网时长已用完",
+        SwitchReason::Reset => "校园网时长已重置",
+    }
+}
+
+/// 通知渠道
+pub trait Notifier: Send + Sync {
+    fn notify(&self, event: &NotificationEvent);
+}
+
+/// 可以在配置中选择的通知渠道
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifierKind {
+    /// 系统桌面通知，受 `enable_toast` 控制
+    Desktop,
+    /// 输出到标准输出
+    Stdout,
+    /// 追加到文件
+    File,
+    /// 发送到配置的 Webhook
+    Webhook,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// 启用的通知渠道，可以组合使用
+    pub sinks: Vec<NotifierKind>,
+    /// `file` 渠道写入的文件，为空则写到数据目录下
+    pub file: String,
+    /// 按事件名自定义的模板，如 `[notification.templates.login_success]`
+    pub templates: BTreeMap<String, NotificationTemplate>,
+}
+
+/// 自定义的通知模板，可以使用 `{name}` `{greeting}` `{service}` `{left_hour}` `{error}` `{tip}` 等占位符，
+/// 留空的部分使用内置模板
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationTemplate {
+    pub title: String,
+    /// 多行正文用 `\n` 分隔
+    pub body: String,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            sinks: vec![NotifierKind::Desktop, NotifierKind::Webhook],
+            file: String::new(),
+            templates: BTreeMap::new(),
+        }
+    }
+}
+
+/// 按配置重新创建通知渠道，应在解析完命令行参数后调用
+pub fn init(config: &AppConfig) {
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![];
+
+    for kind in &config.notification.sinks {
+        match kind {
+            NotifierKind::Desktop => notifiers.push(Box::new(DesktopNotifier {
+                enabled: config.enable_toast,
+                actions: CLI.daemon,
+            })),
+            NotifierKind::Stdout => notifiers.push(Box::new(StdoutNotifier)),
+            NotifierKind::File => notifiers.push(Box::new(FileNotifier {
+                path: (!config.notification.file.is_empty())
+                    .then(|| PathBuf::from(&config.notification.file)),
+            })),
+            NotifierKind::Webhook => {
+                for hook in &config.webhooks {
+                    notifiers.push(Box::new(hook.clone()));
+                }
+            }
+        }
+    }
+
+    *NOTIFIERS.write().unwrap() = notifiers;
+    *SETTINGS.write().unwrap() = config.notification.clone();
+}
+
+/// 把事件发给所有启用的通知渠道
+pub fn notify(event: NotificationEvent) {
+    match &event {
+        NotificationEvent::Error { message } => tracing::error!(message, "程序出错"),
+        NotificationEvent::LoginFailure { error } => tracing::warn!(error, "登录失败"),
+        _ => tracing::info!(event = event.name(), "发送通知"),
+    }
+    // 界面因缺少中文字体改用英文时，通知仍使用配置的语言
+    i18n::with_configured_language(|| {
+        for notifier in NOTIFIERS.read().unwrap().iter() {
+            notifier.notify(&event);
+        }
+    });
+}
+
+/// 替换模板中的 `{key}` 占位符，值经过 `escape` 处理，未知的占位符原样保留
+pub fn fill_template(
+    template: &str,
+    event: &NotificationEvent,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    fill_with(template, |key| event.field(key), escape)
+}
+
+fn fill_with(
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<String>,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let replaced = rest
+            .find('}')
+            .and_then(|end| Some((lookup(&rest[1..end])?, end)));
+        match replaced {
+            Some((value, end)) => {
+                out.push_str(&escape(&value));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn format_line(event: &NotificationEvent) -> String {
+    format!(
+        "[{}] {}: {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        event.title(),
+        event.body().join(" / ")
+    )
+}
+
+struct StdoutNotifier;
+
+impl Notifier for StdoutNotifier {
+    fn notify(&self, event: &NotificationEvent) {
+        println!("{}", format_line(event));
+    }
+}
+
+struct FileNotifier {
+    path: Option<PathBuf>,
+}
+
+impl Notifier for FileNotifier {
+    fn notify(&self, event: &NotificationEvent) {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => match data_dir() {
+                Ok(dir) => dir.join(NOTIFICATION_LOG_FILE_NAME),
+                Err(_) => return,
+            },
+        };
+
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+            writeln!(file, "{}", format_line(event)).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login_success(tip: &str) -> NotificationEvent {
+        NotificationEvent::LoginSuccess {
+            name: "张三".into(),
+            tip: tip.into(),
+            service: Service::Internet,
+            left_hour: Some(12.5),
+        }
+    }
+
+    #[test]
+    fn fills_known_fields() {
+        let event = login_success("早上好");
+        assert_eq!(
+            fill_template("{greeting} ({left_hour})", &event, str::to_string),
+            "张三, 早上好 (12.5)"
+        );
+    }
+
+    #[test]
+    fn greeting_without_tip() {
+        let event = login_success("");
+        assert_eq!(fill_template("{greeting}", &event, str::to_string), "张三");
+    }
+
+    #[test]
+    fn keeps_unknown_and_unclosed_placeholders() {
+        // 其它事件的字段留空，不认识的占位符原样保留
+        let event = NotificationEvent::LoginFailure {
+            error: "超时".into(),
+        };
+        assert_eq!(
+            fill_template("{error} {left_hour} {unknown} {", &event, str::to_string),
+            "超时  {unknown} {"
+        );
+    }
+
+    #[test]
+    fn escapes_values_only() {
+        let event = NotificationEvent::LoginFailure {
+            error: "a\"b".into(),
+        };
+        let escape = |s: &str| s.replace('"', "\\\"");
+        assert_eq!(fill_template("\"{error}\"", &event, escape), "\"a\\\"b\"");
+    }
+}
@@ -0,0 +1,96 @@
+//! 登录时从外部来源读取密码
+//!
+//! 密码可以来自标准输入、文件或命令的输出 (如 `pass show scunet`)，
+//! 这些密码只在登录时读取，不会保存到配置文件中
+
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result, bail};
+use scunet_login_util::LoginError;
+
+use crate::{AppConfig, cli::CLI, hooks, i18n::t};
+
+/// 标准输入只能读一次
+static STDIN: OnceLock<String> = OnceLock::new();
+
+/// 上次运行的命令和它输出的密码，避免后台模式每次检查都运行一次命令；
+/// 登录被拒绝时清空，下次重新运行命令
+static COMMAND_OUTPUT: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+/// 是否配置了外部的密码来源，此时配置中的 `password` 不再使用
+pub fn has_external_source(config: &AppConfig) -> bool {
+    CLI.password_stdin || !config.password_command.is_empty() || !config.password_file.is_empty()
+}
+
+/// 得到登录用的密码
+///
+/// 优先级：标准输入 > `password_command` > `password_file` > 配置中的 `password`
+pub fn resolve(config: &AppConfig) -> Result<String> {
+    let password = if CLI.password_stdin {
+        read_stdin()?
+    } else if !config.password_command.is_empty() {
+        run_command(&config.password_command)?
+    } else if !config.password_file.is_empty() {
+        let content = std::fs::read_to_string(&config.password_file)
+            .with_context(|| format!("{} ({})", t("无法读取密码文件"), config.password_file))?;
+        first_line(&content)
+    } else {
+        return Ok(config.password.clone());
+    };
+
+    if password.is_empty() {
+        bail!(t("外部来源提供的密码为空"));
+    }
+    Ok(password)
+}
+
+/// 登录被拒绝时密码可能已经更换，忘掉命令输出的旧密码
+pub fn forget_if_rejected<T>(result: &Result<T>) {
+    if let Err(e) = result
+        && let Some(LoginError::Fail(_)) = e.downcast_ref::<LoginError>()
+    {
+        *COMMAND_OUTPUT.lock().unwrap() = None;
+    }
+}
+
+fn read_stdin() -> Result<String> {
+    if let Some(password) = STDIN.get() {
+        return Ok(password.clone());
+    }
+
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .context(t("无法从标准输入读取密码"))?;
+    Ok(STDIN.get_or_init(|| first_line(&line)).clone())
+}
+
+fn run_command(command: &str) -> Result<String> {
+    let mut cache = COMMAND_OUTPUT.lock().unwrap();
+    if let Some((cached, password)) = cache.as_ref()
+        && cached == command
+    {
+        return Ok(password.clone());
+    }
+
+    let output = hooks::shell(command)
+        .output()
+        .with_context(|| format!("{} ({})", t("密码命令执行失败"), command))?;
+    if !output.status.success() {
+        bail!(
+            "{} ({}): {}",
+            t("密码命令执行失败"),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let password = first_line(&String::from_utf8_lossy(&output.stdout));
+    *cache = Some((command.to_string(), password.clone()));
+    Ok(password)
+}
+
+/// 只取第一行，像 `pass` 这样的工具会在后面附加其它信息
+fn first_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().to_string()
+}
@@ -0,0 +1,176 @@
+//! 导出和导入配置，方便在多台电脑上使用同一套设置
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    AppConfig,
+    config::{VERSION, parse_config},
+    credentials,
+    i18n::t,
+    schema::CONFIG_VERSION,
+};
+
+/// 导出配置，`redact` 时去掉学号、密码、HTTP 接口的 token、Webhook 和钩子命令
+///
+/// Webhook 地址和钩子命令中常带有密钥，只能整个去掉
+///
+/// 加密保存的账号导出后仍是加密的，导入时需要同一个主密码
+pub fn export(config: &AppConfig, path: &Path, redact: bool) -> Result<()> {
+    let mut config = AppConfig {
+        version: CONFIG_VERSION,
+        // 窗口位置只对本机有意义
+        window_pos: None,
+        window_size: None,
+        ..config.clone()
+    };
+    if redact {
+        config.student_id.clear();
+        config.password.clear();
+        config.credentials = None;
+        config.http_api.token.clear();
+        config.webhooks.clear();
+        config.hooks.on_success.clear();
+        config.hooks.on_failure.clear();
+        config.hooks.on_logout.clear();
+    } else {
+        credentials::seal(&mut config)?;
+    }
+
+    let contents = format!(
+        "# AutoSCUNET v{} {}\n{}",
+        VERSION,
+        t("导出的配置"),
+        toml::to_string(&config)?
+    );
+    std::fs::write(path, contents)
+        .with_context(|| format!("{} ({})", t("导出配置失败"), path.display()))
+}
+
+/// 读取导出的配置，并与本机的配置合并
+///
+/// 窗口位置、开机启动等只与本机有关的设置保持不变；导出时去掉的账号、token、Webhook 和钩子命令沿用本机的
+pub fn import(current: &AppConfig, path: &Path) -> Result<AppConfig> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("{} ({})", t("导入配置失败"), path.display()))?;
+    let mut config = parse_config(&source)
+        .with_context(|| format!("{} ({})", t("导入配置失败"), path.display()))?;
+    credentials::open(&mut config)?;
+
+    config.on_boot = current.on_boot;
+    config.window_pos = current.window_pos;
+    config.window_size = current.window_size;
+    if config.student_id.is_empty() && config.credentials.is_none() {
+        config.student_id = current.student_id.clone();
+        config.password = current.password.clone();
+        config.credentials = current.credentials.clone();
+    }
+    if config.http_api.token.is_empty() {
+        config.http_api.token = current.http_api.token.clone();
+    }
+    if config.webhooks.is_empty() {
+        config.webhooks = current.webhooks.clone();
+    }
+    let hooks = &config.hooks;
+    if hooks.on_success.is_empty() && hooks.on_failure.is_empty() && hooks.on_logout.is_empty() {
+        config.hooks = current.hooks.clone();
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webhook::WebhookConfig;
+
+    use super::*;
+
+    fn current() -> AppConfig {
+        let mut config = AppConfig {
+            student_id: "2024141460000".into(),
+            password: "local".into(),
+            on_boot: true,
+            window_pos: Some([1.0, 2.0]),
+            ..AppConfig::default()
+        };
+        config.http_api.token = "local-token".into();
+        config.hooks.on_success = "curl https://example.com/hook?key=secret".into();
+        config.webhooks.push(WebhookConfig {
+            url: "https://api.day.app/secret-key/{title}".into(),
+            ..WebhookConfig::default()
+        });
+        config
+    }
+
+    fn import_str(name: &str, source: &str) -> AppConfig {
+        let path = std::env::temp_dir().join(format!(
+            "auto-scunet-import-{}-{}.toml",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, source).unwrap();
+        let config = import(&current(), &path);
+        std::fs::remove_file(&path).ok();
+        config.unwrap()
+    }
+
+    #[test]
+    fn redacted_export_keeps_local_account() {
+        let config = import_str("redacted", "version = 1\ngreeting_name = \"导入\"\n");
+        assert_eq!(config.greeting_name, "导入");
+        assert_eq!(config.student_id, "2024141460000");
+        assert_eq!(config.password, "local");
+        assert_eq!(config.http_api.token, "local-token");
+        assert_eq!(config.webhooks.len(), 1);
+        assert_eq!(config.hooks.on_success, current().hooks.on_success);
+    }
+
+    #[test]
+    fn redacted_export_leaves_out_secrets() {
+        let path =
+            std::env::temp_dir().join(format!("auto-scunet-export-{}.toml", std::process::id()));
+        export(&current(), &path, true).unwrap();
+        let exported = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).ok();
+
+        let exported = exported.unwrap();
+        for secret in ["2024141460000", "local", "secret"] {
+            assert!(!exported.contains(secret), "{}", exported);
+        }
+    }
+
+    #[test]
+    fn imported_account_replaces_local() {
+        let source = "\
+version = 1
+student_id = \"2023141460001\"
+password = \"imported\"
+
+[http_api]
+token = \"imported-token\"
+";
+        let config = import_str("account", source);
+        assert_eq!(config.student_id, "2023141460001");
+        assert_eq!(config.password, "imported");
+        assert_eq!(config.http_api.token, "imported-token");
+    }
+
+    #[test]
+    fn machine_settings_stay_local() {
+        let source = "version = 1\nwindow_pos = [100.0, 200.0]\n";
+        let config = import_str("machine", source);
+        assert!(config.on_boot);
+        assert_eq!(config.window_pos, Some([1.0, 2.0]));
+    }
+
+    #[test]
+    fn invalid_file_is_rejected() {
+        let path = std::env::temp_dir().join(format!(
+            "auto-scunet-import-{}-invalid.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "student_id = \"abc\"\n").unwrap();
+        assert!(import(&current(), &path).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,170 @@
+//! 按星期和时段自动登录、注销的计划任务
+
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use scunet_login_util::Service;
+
+/// 一条计划任务规则，在 `login_at` 到 `logout_at` 之间保持登录
+///
+/// `logout_at` 早于 `login_at` 时视为跨越午夜，如 22:00 - 06:00
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleRule {
+    /// 生效的星期 (按登录时间所在的那一天算)，为空则每天生效
+    pub days: Vec<Weekday>,
+    /// 登录时间，格式为 `HH:MM`
+    pub login_at: String,
+    /// 注销时间，格式为 `HH:MM`
+    pub logout_at: String,
+    /// 该时段使用的服务
+    pub service: Service,
+}
+
+impl Default for ScheduleRule {
+    fn default() -> Self {
+        Self {
+            days: vec![],
+            login_at: "07:00".into(),
+            logout_at: "23:30".into(),
+            service: Service::Internet,
+        }
+    }
+}
+
+impl ScheduleRule {
+    /// 时间格式是否正确
+    pub fn is_valid(&self) -> bool {
+        parse_time(&self.login_at).is_some() && parse_time(&self.logout_at).is_some()
+    }
+
+    /// 给定时间是否处于该规则的登录时段内
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        let (Some(login_at), Some(logout_at)) =
+            (parse_time(&self.login_at), parse_time(&self.logout_at))
+        else {
+            return false;
+        };
+
+        let time = now.time();
+        if login_at <= logout_at {
+            self.applies_on(now.weekday()) && login_at <= time && time < logout_at
+        } else {
+            // 跨越午夜的时段，午夜之后的部分属于前一天的规则
+            (self.applies_on(now.weekday()) && time >= login_at)
+                || (self.applies_on((now - Duration::days(1)).weekday()) && time < logout_at)
+        }
+    }
+
+    fn applies_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+}
+
+/// 根据计划任务得出当前应使用的服务，返回 `None` 表示此时应保持注销
+///
+/// 多条规则重叠时以靠前的为准
+pub fn desired_service(rules: &[ScheduleRule], now: NaiveDateTime) -> Option<Service> {
+    rules
+        .iter()
+        .find(|rule| rule.contains(now))
+        .map(|rule| rule.service)
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    /// 2024-01-01 是星期一
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    fn rule(days: Vec<Weekday>, login_at: &str, logout_at: &str) -> ScheduleRule {
+        ScheduleRule {
+            days,
+            login_at: login_at.into(),
+            logout_at: logout_at.into(),
+            service: Service::Internet,
+        }
+    }
+
+    #[test]
+    fn days_can_be_omitted() {
+        let rule: ScheduleRule =
+            toml::from_str("login_at = \"08:00\"\nlogout_at = \"18:00\"").unwrap();
+        assert!(rule.days.is_empty());
+        assert!(rule.contains(at(6, 12, 0)));
+    }
+
+    #[test]
+    fn same_day_window() {
+        let rule = rule(vec![], "07:00", "23:30");
+        assert!(!rule.contains(at(1, 6, 59)));
+        assert!(rule.contains(at(1, 7, 0)));
+        assert!(rule.contains(at(1, 23, 29)));
+        assert!(!rule.contains(at(1, 23, 30)));
+    }
+
+    #[test]
+    fn window_past_midnight() {
+        let rule = rule(vec![], "22:00", "06:00");
+        assert!(rule.contains(at(1, 22, 0)));
+        assert!(rule.contains(at(2, 0, 30)));
+        assert!(rule.contains(at(2, 5, 59)));
+        assert!(!rule.contains(at(2, 6, 0)));
+        assert!(!rule.contains(at(2, 12, 0)));
+    }
+
+    #[test]
+    fn window_past_midnight_belongs_to_login_day() {
+        let rule = rule(vec![Weekday::Fri], "22:00", "06:00");
+        // 星期五晚上到星期六早上
+        assert!(rule.contains(at(5, 23, 0)));
+        assert!(rule.contains(at(6, 1, 0)));
+        // 星期四晚上和星期五早上都不算
+        assert!(!rule.contains(at(4, 23, 0)));
+        assert!(!rule.contains(at(5, 1, 0)));
+    }
+
+    #[test]
+    fn days_filter() {
+        let rule = rule(vec![Weekday::Mon], "07:00", "23:30");
+        assert!(rule.contains(at(1, 12, 0)));
+        assert!(!rule.contains(at(2, 12, 0)));
+    }
+
+    #[test]
+    fn invalid_time_never_matches() {
+        let rule = rule(vec![], "7 点", "23:30");
+        assert!(!rule.is_valid());
+        assert!(!rule.contains(at(1, 12, 0)));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut campus = rule(vec![], "00:00", "23:59");
+        campus.service = Service::Internet;
+        let mut telecom = rule(vec![], "08:00", "18:00");
+        telecom.service = Service::ChinaTelecom;
+        let rules = [telecom, campus];
+        assert_eq!(
+            desired_service(&rules, at(1, 12, 0)),
+            Some(Service::ChinaTelecom)
+        );
+        assert_eq!(
+            desired_service(&rules, at(1, 20, 0)),
+            Some(Service::Internet)
+        );
+        assert_eq!(desired_service(&rules[..1], at(1, 20, 0)), None);
+    }
+}
@@ -0,0 +1,241 @@
+//! 配置文件的版本迁移与校验
+
+use std::net::SocketAddr;
+
+use anyhow::{Result, bail};
+use toml::{Table, Value};
+
+use crate::{
+    AppConfig,
+    i18n::{t, tf},
+};
+
+use scunet_login_util::Service;
+
+/// 当前的配置文件版本
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 把旧版本的配置升级到当前版本
+pub fn migrate(table: &mut Table) -> Result<()> {
+    let version = table
+        .get("version")
+        .and_then(Value::as_integer)
+        .unwrap_or(0);
+
+    if version > CONFIG_VERSION as i64 {
+        bail!(tf(
+            "配置文件版本 {} 高于程序支持的版本 {}，请更新程序",
+            &[&version, &CONFIG_VERSION]
+        ));
+    }
+    // v0 (没有 version 字段) 与 v1 的格式相同，只需写入版本号，以后的格式变化在这里逐版本迁移
+
+    table.insert("version".into(), Value::Integer(CONFIG_VERSION.into()));
+    Ok(())
+}
+
+/// 出错的配置项所在的位置
+struct Location<'a> {
+    /// 所在的表，`[[表]]` 形式的还要带上序号
+    table: Option<(&'a str, Option<usize>)>,
+    key: &'a str,
+}
+
+impl<'a> Location<'a> {
+    fn root(key: &'a str) -> Self {
+        Self { table: None, key }
+    }
+
+    fn table(table: &'a str, key: &'a str) -> Self {
+        Self {
+            table: Some((table, None)),
+            key,
+        }
+    }
+
+    fn item(table: &'a str, index: usize, key: &'a str) -> Self {
+        Self {
+            table: Some((table, Some(index))),
+            key,
+        }
+    }
+}
+
+/// 检查配置中的取值，返回带行号的错误信息
+pub fn validate(config: &AppConfig, source: &str) -> Vec<String> {
+    let mut errors = vec![];
+    let mut check = |ok: bool, location: Location, message: &str| {
+        if !ok {
+            errors.push(with_line(find_line(source, &location), t(message)));
+        }
+    };
+
+    check(
+        config.student_id.chars().all(|c| c.is_ascii_digit()),
+        Location::root("student_id"),
+        "学号只能包含数字",
+    );
+    check(
+        (0.0..=5.0).contains(&config.on_boot_delay),
+        Location::root("on_boot_delay"),
+        "开机启动延迟应在 0 到 5 秒之间",
+    );
+    check(
+        config.low_hour_threshold >= 0.0,
+        Location::root("low_hour_threshold"),
+        "低时长提醒不能为负数",
+    );
+    check(
+        config.auto_switch_service != Some(Service::Internet),
+        Location::root("auto_switch_service"),
+        "时长用完后只能切换到运营商服务",
+    );
+    check(
+        config.watch_interval >= 10,
+        Location::root("watch_interval"),
+        "检查间隔不能小于 10 秒",
+    );
+    check(
+        (0.5..=3.0).contains(&config.ui_scale),
+        Location::root("ui_scale"),
+        "界面缩放应在 0.5 到 3 之间",
+    );
+    check(
+        config.http_api.listen.parse::<SocketAddr>().is_ok(),
+        Location::table("http_api", "listen"),
+        "监听地址格式应为 IP:端口",
+    );
+    check(
+        config.metrics.listen.parse::<SocketAddr>().is_ok(),
+        Location::table("metrics", "listen"),
+        "监听地址格式应为 IP:端口",
+    );
+    check(
+        config.log.level_filter().is_some(),
+        Location::table("log", "level"),
+        "日志级别应为 error、warn、info、debug 或 trace",
+    );
+    for (i, rule) in config.schedule.iter().enumerate() {
+        check(
+            rule.is_valid(),
+            Location::item("schedule", i, "login_at"),
+            "时间格式应为 HH:MM",
+        );
+    }
+    for (i, hook) in config.webhooks.iter().enumerate() {
+        check(
+            hook.url.starts_with("http://") || hook.url.starts_with("https://"),
+            Location::item("webhooks", i, "url"),
+            "Webhook 地址应以 http:// 或 https:// 开头",
+        );
+    }
+
+    errors
+}
+
+/// 把 TOML 解析错误转换成带行号的错误信息
+pub fn parse_error(source: &str, e: &toml::de::Error) -> String {
+    let line = e
+        .span()
+        .map(|span| source[..span.start].matches('\n').count() + 1);
+    with_line(line, e.message())
+}
+
+fn with_line(line: Option<usize>, message: &str) -> String {
+    match line {
+        Some(line) => tf("第 {} 行: {}", &[&line, &message]),
+        None => message.to_string(),
+    }
+}
+
+/// 找到配置项所在的行，文件中没有写出该项时返回 `None`
+fn find_line(source: &str, location: &Location) -> Option<usize> {
+    let mut in_table = location.table.is_none();
+    let mut item_index = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            let name = line.trim_matches(['[', ']']).trim();
+            in_table = match location.table {
+                Some((table, None)) => name == table,
+                Some((table, Some(index))) if name == table && line.starts_with("[[") => {
+                    item_index += 1;
+                    item_index == index + 1
+                }
+                _ => false,
+            };
+            continue;
+        }
+
+        let key = line.split_once('=').map(|(key, _)| key.trim());
+        if in_table && key == Some(location.key) {
+            return Some(i + 1);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<String> {
+        let config: AppConfig = toml::from_str(source).unwrap();
+        validate(&config, source)
+    }
+
+    #[test]
+    fn valid_config() {
+        assert!(check("student_id = \"2024141460000\"\n").is_empty());
+        assert!(check("").is_empty());
+    }
+
+    #[test]
+    fn reports_line_of_root_key() {
+        let errors = check("version = 1\nstudent_id = \"abc\"\n");
+        assert_eq!(errors, ["第 2 行: 学号只能包含数字"]);
+    }
+
+    #[test]
+    fn reports_line_inside_table() {
+        let source = "ui_scale = 1.0\n\n[log]\nlevel = \"loud\"\n";
+        assert_eq!(
+            check(source),
+            ["第 4 行: 日志级别应为 error、warn、info、debug 或 trace"]
+        );
+    }
+
+    #[test]
+    fn reports_line_of_array_item() {
+        let source = "\
+[[webhooks]]
+url = \"https://example.com\"
+
+[[webhooks]]
+url = \"ftp://example.com\"
+";
+        assert_eq!(
+            check(source),
+            ["第 5 行: Webhook 地址应以 http:// 或 https:// 开头"]
+        );
+    }
+
+    #[test]
+    fn migrate_adds_version() {
+        let mut table: Table = toml::from_str("student_id = \"1\"").unwrap();
+        migrate(&mut table).unwrap();
+        assert_eq!(
+            table.get("version"),
+            Some(&Value::Integer(CONFIG_VERSION.into()))
+        );
+    }
+
+    #[test]
+    fn migrate_rejects_newer_version() {
+        let mut table: Table = toml::from_str("version = 99").unwrap();
+        assert!(migrate(&mut table).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! 校园网时长用完时自动切换到运营商服务，并在每月重置后切换回来
+
+use anyhow::Result;
+use chrono::{Datelike, Local, TimeZone};
+
+use crate::{
+    AppConfig,
+    history::{load_history, save_history},
+    i18n::error_text,
+    logout,
+    notification::{NotificationEvent, SwitchReason, notify},
+};
+
+use scunet_login_util::*;
+
+/// 决定本次登录应使用的服务
+///
+/// 本月已经切换过运营商时继续使用运营商，避免每次都先用校园网登录一遍；
+/// 如果已经到了下个月并且开启了自动切换回来，则先注销运营商再改用校园网
+pub fn prepare_service(config: &AppConfig) -> Service {
+    let mut history = load_history().unwrap_or_default();
+
+    let (Some(switched_at), Some(carrier)) = (history.switched_at, config.auto_switch_service)
+    else {
+        return config.service;
+    };
+
+    if !has_reset_since(switched_at) || !config.switch_back_on_reset {
+        return carrier;
+    }
+
+    history.switched_at = None;
+    save_history(&history).ok();
+
+    match logout(config, carrier) {
+        Ok(_) => notify(NotificationEvent::ServiceSwitched {
+            from: carrier,
+            to: config.service,
+            reason: SwitchReason::Reset,
+        }),
+        Err(e) => notify(NotificationEvent::LoginFailure {
+            error: error_text(&e),
+        }),
+    }
+
+    config.service
+}
+
+/// 登录成功后检查校园网剩余时长，用完时注销并改用运营商服务登录
+///
+/// 返回最终的登录信息，未切换时原样返回
+pub fn apply(config: &AppConfig, user_info: OnlineUserInfo) -> OnlineUserInfo {
+    let Some(carrier) = config.auto_switch_service else {
+        return user_info;
+    };
+
+    let exhausted = user_info.left_hour.is_some_and(|h| h <= 0.0);
+    if user_info.service != Service::Internet || !exhausted {
+        return user_info;
+    }
+
+    match switch_to(config, &user_info.encrypted_password, carrier) {
+        Ok(new_info) => {
+            let mut history = load_history().unwrap_or_default();
+            history.switched_at = Some(Local::now().timestamp());
+            save_history(&history).ok();
+
+            notify(NotificationEvent::ServiceSwitched {
+                from: Service::Internet,
+                to: carrier,
+                reason: SwitchReason::Exhausted,
+            });
+            new_info
+        }
+        Err(e) => {
+            notify(NotificationEvent::LoginFailure {
+                error: error_text(&e),
+            });
+            user_info
+        }
+    }
+}
+
+fn switch_to(config: &AppConfig, password: &str, service: Service) -> Result<OnlineUserInfo> {
+    logout(config, Service::Internet)?;
+
+    let mut login_util = ScunetLoginUtil::builder()
+        .student_id(&config.student_id)
+        .password(password)
+        .service(service)
+        .build();
+
+    match login_util.login()? {
+        LoginStatus::Success(user_info) => Ok(user_info),
+        LoginStatus::HaveLoggedIn => {
+            Err(LoginError::Fail("注销失败，仍处于登录状态".into()).into())
+        }
+    }
+}
+
+/// 自切换以来是否已经跨月 (校园网时长按自然月重置)
+fn has_reset_since(timestamp: i64) -> bool {
+    let Some(then) = Local.timestamp_opt(timestamp, 0).single() else {
+        return true;
+    };
+    let now = Local::now();
+
+    (now.year(), now.month()) != (then.year(), then.month())
+}
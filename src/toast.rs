@@ -1,56 +1,59 @@
-use crate::{AppConfig, config::IS_TOAST_ENABLED};
-use scunet_login_util::Service;
-
-pub struct Toast;
-
-impl Toast {
-    pub fn success(
-        name: String,
-        tip: String,
-        time: Option<f64>,
-        service: Service,
-        config: &AppConfig,
-    ) {
-        if !config.enable_toast {
+use crate::notification::{NotificationAction, NotificationEvent, Notifier};
+
+/// 系统桌面通知
+pub struct DesktopNotifier {
+    /// 对应设置中的 "启用通知"，程序出错的通知不受影响
+    pub enabled: bool,
+    /// 是否显示操作按钮
+    ///
+    /// 按钮由本进程响应，只有后台模式会一直运行，其它情况下发完通知程序就退出了
+    pub actions: bool,
+}
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, event: &NotificationEvent) {
+        if !self.enabled && !matches!(event, NotificationEvent::Error { .. }) {
             return;
         }
 
-        let main_msg = format!("你已登录到 SCUNET ({})", service.to_str());
-        let mut messages = vec![main_msg];
+        let title = event.title();
+        let body = event.body();
+        let body = body.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
 
-        if let Some(t) = time {
-            let left_hour_msg = format!("剩余时间: {} 小时", t);
-            messages.push(left_hour_msg);
+        #[cfg(windows)]
+        if let NotificationEvent::Error { .. } = event {
+            _error(&title, body);
+            return;
         }
 
-        let messages = messages.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-
-        let name = if config.greeting_name.is_empty() {
-            name
+        let actions = if self.actions {
+            event.actions()
         } else {
-            config.greeting_name.clone()
+            vec![]
         };
-
-        _success(&format!("{}, {}", name, tip), messages);
-    }
-
-    pub fn fail(msg: impl ToString) {
-        if !*IS_TOAST_ENABLED.read().unwrap() {
-            return;
-        }
-        _fail(msg);
+        _show(&title, body, &actions);
     }
+}
 
-    pub fn logged_in() {
-        if !*IS_TOAST_ENABLED.read().unwrap() {
+/// 执行通知上被点击的操作，除了打开网页都转发给正在运行的实例
+#[cfg(all(unix, not(target_os = "macos")))]
+fn perform(action: NotificationAction) {
+    use crate::{
+        config::GITHUB_REPO,
+        instance::{self, Command},
+    };
+    use scunet_login_util::Service;
+
+    let command = match action {
+        NotificationAction::Retry => Command::Login,
+        NotificationAction::SwitchToCampus => Command::SwitchService(Service::Internet),
+        NotificationAction::Logout => Command::Logout,
+        NotificationAction::OpenIssuePage => {
+            webbrowser::open(&format!("{}/issues", GITHUB_REPO)).ok();
             return;
         }
-        _logged_in();
-    }
-
-    pub fn error(msg: impl ToString) {
-        _error(msg);
-    }
+    };
+    instance::send(command).ok();
 }
 
 #[cfg(windows)]
@@ -66,67 +69,77 @@ fn new_toast() -> WinToastNotify {
         .set_notif_open("")
 }
 
-fn _success(title: &str, body: Vec<&str>) {
-    #[cfg(windows)]
+#[cfg(windows)]
+fn _show(title: &str, body: Vec<&str>, _actions: &[NotificationAction]) {
     new_toast().set_title(title).set_messages(body).show().ok();
-    #[cfg(not(windows))]
-    Notification::new()
-        .summary(title)
-        .body(&body.join("\n"))
-        .show()
-        .ok();
 }
 
-fn _fail(msg: impl ToString) {
-    #[cfg(windows)]
-    new_toast()
-        .set_title("登录失败")
-        .set_messages(vec![&msg.to_string(), "请手动调整配置或检查网络状态"])
-        .show()
-        .ok();
-    #[cfg(not(windows))]
-    Notification::new()
-        .summary("登录失败")
-        .body(&format!(
-            "{}\n请手动调整配置或检查网络状态",
-            msg.to_string()
-        ))
-        .show()
-        .ok();
-}
-
-fn _logged_in() {
-    #[cfg(windows)]
-    new_toast()
-        .set_title("你已登录到 SCUNET")
-        .set_messages(vec!["你可以再次\"登录\"来更新配置"])
-        .show()
-        .ok();
-    #[cfg(not(windows))]
-    Notification::new()
-        .summary("你已登录到 SCUNET")
-        .body("你可以再次\"登录\"来更新配置")
-        .show()
-        .ok();
-}
-
-fn _error(msg: impl ToString) {
-    #[cfg(windows)]
+#[cfg(windows)]
+fn _error(title: &str, body: Vec<&str>) {
     new_toast()
-        .set_title("😭😭😭 程序出错了")
-        .set_messages(vec![&msg.to_string(), "可以考虑提一个 Issue"])
+        .set_title(title)
+        .set_messages(body)
         .set_actions(vec![Action {
             activation_type: win_toast_notify::ActivationType::Protocol,
-            action_content: "打开 GitHub Issue 页",
+            action_content: NotificationAction::OpenIssuePage.label(),
             arguments: "https://www.github.com/EastMonster/auto-scunet/issues",
             image_url: None,
         }])
         .show()
         .ok();
-    #[cfg(not(windows))]
+}
+
+#[cfg(target_os = "macos")]
+fn _show(title: &str, body: Vec<&str>, _actions: &[NotificationAction]) {
     Notification::new()
-        .summary("😭😭😭 程序出错了")
-        .body(&format!("{}\n可以考虑提一个 Issue", msg.to_string()))
+        .summary(title)
+        .body(&body.join("\n"))
         .show()
         .ok();
 }
+
+/// 最近一条通知的 id，新的通知会替换掉它而不是叠在一起
+#[cfg(all(unix, not(target_os = "macos")))]
+static LAST_ID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+/// 每显示一条通知加一，被替换掉的通知不再响应操作
+#[cfg(all(unix, not(target_os = "macos")))]
+static GENERATION: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn _show(title: &str, body: Vec<&str>, actions: &[NotificationAction]) {
+    use std::sync::atomic::Ordering;
+
+    let mut notification = Notification::new();
+    notification.summary(title).body(&body.join("\n"));
+    for action in actions {
+        notification.action(action.id(), action.label());
+    }
+    match LAST_ID.load(Ordering::Relaxed) {
+        0 => {}
+        id => {
+            notification.id(id);
+        }
+    }
+
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    let Ok(handle) = notification.show() else {
+        return;
+    };
+    LAST_ID.store(handle.id(), Ordering::Relaxed);
+
+    if actions.is_empty() {
+        return;
+    }
+    // 等待操作会一直阻塞到通知关闭
+    std::thread::spawn(move || {
+        handle.wait_for_action(|id| {
+            if GENERATION.load(Ordering::Relaxed) != generation {
+                return;
+            }
+            if let Some(action) = NotificationAction::from_id(id) {
+                perform(action);
+            }
+        });
+    });
+}
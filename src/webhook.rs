@@ -0,0 +1,164 @@
+//! 登录事件的 Webhook 通知，适合没有桌面通知的无头设备
+//!
+//! 请求体模板中可以使用以下占位符，替换时会按 JSON 字符串转义：
+//! `{event}` `{title}` `{message}` `{name}` `{tip}` `{greeting}` `{service}` `{from}` `{left_hour}`
+//! `{exhaustion}` `{suggestion}` `{reason}` `{error}` `{time}`
+
+use std::{
+    sync::Mutex,
+    thread::{self, JoinHandle, sleep},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    join_until,
+    notification::{NotificationEvent, Notifier, fill_template},
+};
+
+const DEFAULT_BODY: &str = r#"{"event": "{event}", "message": "{message}", "time": "{time}"}"#;
+
+/// 程序退出前最多等待 Webhook 发送这么久，至少够一次请求超时
+const WAIT_ON_EXIT: Duration = Duration::from_secs(15);
+
+/// 还在发送的请求，程序退出前要等它们
+static SENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// 单个 Webhook 的配置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// 请求方法，GET 请求不带请求体
+    pub method: String,
+    /// JSON 请求体模板，为空则使用默认模板
+    pub body: String,
+    /// 失败后的重试次数
+    pub retries: u32,
+    pub events: WebhookEvents,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            method: "POST".into(),
+            body: String::new(),
+            retries: 3,
+            events: WebhookEvents::default(),
+        }
+    }
+}
+
+/// 各事件是否触发该 Webhook
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookEvents {
+    pub login_success: bool,
+    pub login_failure: bool,
+    pub session_lost: bool,
+    pub low_hour: bool,
+}
+
+impl Default for WebhookEvents {
+    fn default() -> Self {
+        Self {
+            login_success: true,
+            login_failure: true,
+            session_lost: true,
+            low_hour: true,
+        }
+    }
+}
+
+impl WebhookEvents {
+    fn is_enabled(&self, event: &NotificationEvent) -> bool {
+        match event {
+            NotificationEvent::LoginSuccess { .. } => self.login_success,
+            NotificationEvent::LoginFailure { .. } => self.login_failure,
+            NotificationEvent::SessionLost { .. } => self.session_lost,
+            NotificationEvent::LowHour { .. } => self.low_hour,
+            _ => false,
+        }
+    }
+}
+
+impl Notifier for WebhookConfig {
+    /// 在后台线程中发送请求
+    fn notify(&self, event: &NotificationEvent) {
+        if self.url.is_empty() || !self.events.is_enabled(event) {
+            return;
+        }
+
+        let template = if self.body.is_empty() {
+            DEFAULT_BODY
+        } else {
+            &self.body
+        };
+        let body = fill_template(template, event, json_escape);
+
+        let hook = self.clone();
+        let mut sending = SENDING.lock().unwrap();
+        sending.retain(|handle| !handle.is_finished());
+        sending.push(thread::spawn(move || send(&hook, &body)));
+    }
+}
+
+/// 等待还在发送的请求，最多等 [`WAIT_ON_EXIT`]
+pub fn wait() {
+    let sending = std::mem::take(&mut *SENDING.lock().unwrap());
+    join_until(sending, Instant::now() + WAIT_ON_EXIT);
+}
+
+fn send(hook: &WebhookConfig, body: &str) {
+    let method = hook.method.to_uppercase();
+
+    for attempt in 0..=hook.retries {
+        if attempt > 0 {
+            sleep(Duration::from_secs(1 << (attempt - 1).min(5)));
+        }
+
+        let request = ureq::request(&method, &hook.url).timeout(Duration::from_secs(10));
+        let result = if method == "GET" {
+            request.call()
+        } else {
+            request
+                .set("Content-Type", "application/json")
+                .send_string(body)
+        };
+
+        match result {
+            Ok(_) => return,
+            Err(e) => tracing::warn!(attempt, error = %e, "Webhook 发送失败"),
+        }
+    }
+}
+
+/// 转义成 JSON 字符串的内容 (不带两侧引号)
+fn json_escape(s: &str) -> String {
+    let quoted = serde_json::to_string(s).unwrap();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_escape_without_quotes() {
+        assert_eq!(json_escape("plain"), "plain");
+        assert_eq!(json_escape("a \"b\"\n\\"), "a \\\"b\\\"\\n\\\\");
+        assert_eq!(json_escape("中文"), "中文");
+    }
+
+    #[test]
+    fn template_stays_valid_json() {
+        let event = NotificationEvent::LoginFailure {
+            error: "账号或密码错误\n\"请重试\"".into(),
+        };
+        let body = fill_template(r#"{"error": "{error}"}"#, &event, json_escape);
+        let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(value["error"], "账号或密码错误\n\"请重试\"");
+    }
+}